@@ -0,0 +1,217 @@
+use crate::config::{BackupConfig, BackupMode, StorageConfig};
+use crate::driver::{self, DriverInfo, DRIVERS};
+use crate::manifest::{manifest_name, read_manifest, BackupManifest};
+use crate::storage::{create_storage, ObjectInfo, StorageBackend};
+use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use serde::Serialize;
+use std::collections::HashSet;
+use tracing::{debug, warn};
+
+/// Timestamp format the drivers embed in archive names
+pub const TIMESTAMP_FORMAT: &str = "%Y%m%d_%H%M%S";
+const TIMESTAMP_LEN: usize = 15;
+const DEFAULT_FILENAME_PREFIX: &str = "backup_";
+/// Extension of parallel-mode archives, shared by every driver
+pub const PARALLEL_EXTENSION: &str = ".dir.tar.gz";
+
+/// A backup archive recognized from its file name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BackupArtifact {
+    pub filename: String,
+    pub timestamp: NaiveDateTime,
+    pub mode: BackupMode,
+}
+
+impl BackupArtifact {
+    /// Parse `<filename_prefix><%Y%m%d_%H%M%S>.<dump.gz|sql.gz|dir.tar.gz>`
+    pub fn parse(filename: &str, filename_prefix: &str) -> Option<Self> {
+        let rest = filename.strip_prefix(filename_prefix)?;
+        let timestamp = NaiveDateTime::parse_from_str(rest.get(..TIMESTAMP_LEN)?, TIMESTAMP_FORMAT).ok()?;
+
+        // Nothing but the archive extension may follow the timestamp
+        let mode = match &rest[TIMESTAMP_LEN..] {
+            PARALLEL_EXTENSION => BackupMode::Parallel,
+            extension if DRIVERS.iter().any(|d| d.extension == extension) => BackupMode::Basic,
+            _ => return None,
+        };
+
+        Some(Self {
+            filename: filename.to_string(),
+            timestamp,
+            mode,
+        })
+    }
+
+    /// Whether a database driver writes archives with this file's extension
+    pub fn is_produced_by(&self, driver: &DriverInfo) -> bool {
+        match self.mode {
+            BackupMode::Parallel => true,
+            BackupMode::Basic => self.filename.ends_with(driver.extension),
+        }
+    }
+}
+
+/// An archive found in a storage location
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredArtifact {
+    #[serde(flatten)]
+    pub artifact: BackupArtifact,
+    /// Reference accepted by `restore::fetch_archive` (file name or S3 key)
+    pub key: String,
+    pub location: String,
+    pub size: u64,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+pub fn filename_prefix(storage_config: &StorageConfig) -> &str {
+    storage_config.filename_prefix.as_deref().unwrap_or(DEFAULT_FILENAME_PREFIX)
+}
+
+/// A backup archive attributed to a job, with its sidecar manifest if present
+pub struct JobArtifact {
+    pub artifact: BackupArtifact,
+    pub object: ObjectInfo,
+    pub manifest_key: Option<String>,
+    pub manifest: Option<BackupManifest>,
+    /// When the backup was taken, in local time
+    pub created: NaiveDateTime,
+}
+
+/// Collect the archives in `storage` that belong to `backup`.
+///
+/// An object belongs to the job when its name follows the job's naming
+/// pattern (`<filename_prefix><timestamp>.<ext>` with an extension the job's
+/// driver produces) and, if a manifest exists next to it, the manifest names
+/// this job. Everything else — READMEs, stray files, other jobs' dumps in a
+/// shared location — is left alone.
+pub async fn job_artifacts(
+    storage: &dyn StorageBackend,
+    backup: &BackupConfig,
+    filename_prefix: &str,
+) -> Result<Vec<JobArtifact>> {
+    let driver = driver::lookup(&backup.driver)
+        .ok_or_else(|| anyhow::anyhow!("Unsupported database driver: {}", backup.driver))?;
+    let objects = storage.list(filename_prefix).await?;
+    let keys: HashSet<&str> = objects.iter().map(|o| o.key.as_str()).collect();
+
+    let mut artifacts = Vec::new();
+    for object in &objects {
+        let Some(artifact) = BackupArtifact::parse(&object.key, filename_prefix) else { continue };
+        if !artifact.is_produced_by(driver) {
+            continue;
+        }
+
+        // Backup time comes from the manifest when there is one, otherwise from the
+        // timestamp the driver embedded in the name. Storage mtimes are never used:
+        // copies, bucket syncs and restores from archive tiers reset them.
+        let manifest_key = manifest_name(&object.key);
+        let (manifest_key, manifest, created) = if keys.contains(manifest_key.as_str()) {
+            match read_manifest(storage, &manifest_key).await {
+                Ok(manifest) if manifest.backup == backup.name => {
+                    let created = manifest.started_at.with_timezone(&Local).naive_local();
+                    (Some(manifest_key), Some(manifest), created)
+                }
+                Ok(manifest) => {
+                    debug!("Skipping {}: belongs to backup '{}'", object.key, manifest.backup);
+                    continue;
+                }
+                Err(e) => {
+                    // Never touch an archive we cannot attribute
+                    warn!("Skipping {}: {:#}", object.key, e);
+                    continue;
+                }
+            }
+        } else {
+            (None, None, artifact.timestamp)
+        };
+
+        artifacts.push(JobArtifact { artifact, object: object.clone(), manifest_key, manifest, created });
+    }
+
+    Ok(artifacts)
+}
+
+/// List a backup job's archives in its storage location, oldest first.
+///
+/// Archives of other jobs sharing the location are left out; see [`job_artifacts`].
+pub async fn list_artifacts(backup: &BackupConfig, storage_config: &StorageConfig) -> Result<Vec<StoredArtifact>> {
+    let storage = create_storage(storage_config).await?;
+
+    let mut artifacts: Vec<StoredArtifact> = job_artifacts(storage.as_ref(), backup, filename_prefix(storage_config))
+        .await?
+        .into_iter()
+        .map(|job_artifact| StoredArtifact {
+            artifact: job_artifact.artifact,
+            location: storage.location_of(&job_artifact.object.key),
+            key: job_artifact.object.key,
+            size: job_artifact.object.size,
+            last_modified: job_artifact.object.last_modified,
+        })
+        .collect();
+
+    artifacts.sort_by_key(|stored| stored.artifact.timestamp);
+    Ok(artifacts)
+}
+
+/// Format a byte count for humans, e.g. `1.5 GiB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_parse_artifact() {
+        let artifact = BackupArtifact::parse("backup_20260218_020000.dump.gz", "backup_").unwrap();
+        assert_eq!(
+            artifact.timestamp,
+            NaiveDate::from_ymd_opt(2026, 2, 18).unwrap().and_hms_opt(2, 0, 0).unwrap()
+        );
+        assert_eq!(artifact.mode, BackupMode::Basic);
+
+        let artifact = BackupArtifact::parse("mysql_20260218_020000.dir.tar.gz", "mysql_").unwrap();
+        assert_eq!(artifact.mode, BackupMode::Parallel);
+    }
+
+    #[test]
+    fn test_parse_rejects_foreign_files() {
+        assert!(BackupArtifact::parse("README.md", "backup_").is_none());
+        assert!(BackupArtifact::parse("backup_20260218_020000.dump.gz.manifest.json", "backup_").is_none());
+        assert!(BackupArtifact::parse("mysql_20260218_020000.sql.gz", "backup_").is_none());
+        assert!(BackupArtifact::parse("backup_notadate_xx.dump.gz", "backup_").is_none());
+        assert!(BackupArtifact::parse("backup_20260218_020000_copy.dump.gz", "backup_").is_none());
+    }
+
+    #[test]
+    fn test_is_produced_by() {
+        let dump = BackupArtifact::parse("backup_20260218_020000.dump.gz", "backup_").unwrap();
+        assert!(dump.is_produced_by(&crate::postgres::DRIVER));
+        assert!(!dump.is_produced_by(&crate::mysql::DRIVER));
+
+        let directory = BackupArtifact::parse("backup_20260218_020000.dir.tar.gz", "backup_").unwrap();
+        assert!(directory.is_produced_by(&crate::postgres::DRIVER));
+        assert!(directory.is_produced_by(&crate::mysql::DRIVER));
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+}
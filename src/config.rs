@@ -1,7 +1,10 @@
+use crate::driver::BackupDriver;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::collections::HashMap;
 
+const DEFAULT_STATE_DIR: &str = "/var/lib/dbackup";
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Config {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -15,6 +18,44 @@ pub struct Settings {
     pub binary: Option<BinarySettings>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub storages: Option<HashMap<String, StorageConfig>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restore_targets: Option<HashMap<String, ConnectionOverride>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_dir: Option<PathBuf>,  // defaults to /var/lib/dbackup
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<ConcurrencySettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lock: Option<LockSettings>,
+}
+
+/// How dbackup instances sharing a configuration avoid running the same job twice
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LockSettings {
+    /// Also claim runs through a lease object in the job's S3 storage (multi-host setups)
+    #[serde(default)]
+    pub s3_lease: bool,
+    /// How long a lease outlives its holder; renewed while the backup runs
+    #[serde(default = "default_lease_ttl")]
+    pub lease_ttl: String,
+}
+
+fn default_lease_ttl() -> String {
+    "5m".to_string()
+}
+
+/// Scheduler limits per concurrency group, layered on the global `--concurrency`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ConcurrencySettings {
+    /// Limit of every group without an entry in `groups`
+    #[serde(default = "default_group_limit")]
+    pub per_group: usize,
+    /// Limits by `group:` label or `host:port`
+    #[serde(default)]
+    pub groups: HashMap<String, usize>,
+}
+
+fn default_group_limit() -> usize {
+    1
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -23,21 +64,22 @@ pub struct BinarySettings {
     pub pg_dump: Option<PathBuf>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mysqldump: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pg_restore: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mysql: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub psql: Option<PathBuf>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum BackupMode {
+    #[default]
     Basic,
     Parallel,
 }
 
-impl Default for BackupMode {
-    fn default() -> Self {
-        BackupMode::Basic
-    }
-}
-
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct BackupConfig {
     pub name: String,
@@ -46,6 +88,8 @@ pub struct BackupConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub schedule: Option<ScheduleConfig>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,  // concurrency group; defaults to the server's host:port
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub storage: Option<StorageSelection>,
     #[serde(default)]
     pub mode: BackupMode,
@@ -54,13 +98,72 @@ pub struct BackupConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub binary_path: Option<PathBuf>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub retention: Option<String>,  // e.g., "7d", "30d", "1w", "2mon", "1y"
+    pub timeout: Option<String>,  // e.g., "2h"; the dump is killed when exceeded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retention: Option<RetentionConfig>,  // e.g., "30d" or a keep_* block
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verify: Option<VerifyConfig>,
 }
 
 fn default_parallel_jobs() -> u8 {
     2
 }
 
+/// Retries with exponential backoff for failed backup runs
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RetryConfig {
+    /// Total attempts per phase, including the first
+    #[serde(default = "default_retry_attempts")]
+    pub attempts: u32,
+    #[serde(default = "default_retry_initial_delay")]
+    pub initial_delay: String,
+    #[serde(default = "default_retry_max_delay")]
+    pub max_delay: String,
+    /// Phases that are retried; an upload retry reuses the archive already dumped
+    #[serde(default = "default_retry_phases")]
+    pub phases: Vec<RetryPhase>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RetryPhase {
+    /// Running the dump tool and compressing its output
+    Dump,
+    /// Handing the archive and manifest to storage
+    Upload,
+}
+
+fn default_retry_attempts() -> u32 {
+    3
+}
+
+fn default_retry_initial_delay() -> String {
+    "30s".to_string()
+}
+
+fn default_retry_max_delay() -> String {
+    "10m".to_string()
+}
+
+fn default_retry_phases() -> Vec<RetryPhase> {
+    vec![RetryPhase::Dump, RetryPhase::Upload]
+}
+
+impl BackupConfig {
+    /// Parsed `timeout`, if one is set
+    pub fn timeout(&self) -> anyhow::Result<Option<std::time::Duration>> {
+        self.timeout
+            .as_deref()
+            .map(|timeout| {
+                crate::retention::parse_duration(timeout)
+                    .map_err(|e| e.context(format!("Invalid timeout for backup '{}'", self.name)))
+            })
+            .transpose()
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ConnectionConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -72,9 +175,141 @@ pub struct ConnectionConfig {
     pub database: String,
 }
 
+/// Partial connection settings layered over a backup's own connection
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ConnectionOverride {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub database: Option<String>,
+}
+
+impl ConnectionOverride {
+    /// Apply the overridden fields on top of `base`
+    pub fn apply(&self, base: &ConnectionConfig) -> ConnectionConfig {
+        let mut connection = base.clone();
+        if let Some(host) = &self.host {
+            connection.host = host.clone();
+            // The URI describes the original server; drop it once we point elsewhere
+            connection.uri = None;
+        }
+        if let Some(port) = self.port {
+            connection.port = port;
+        }
+        if let Some(username) = &self.username {
+            connection.username = username.clone();
+        }
+        if let Some(password) = &self.password {
+            connection.password = password.clone();
+        }
+        if let Some(database) = &self.database {
+            connection.database = database.clone();
+        }
+        connection
+    }
+}
+
+/// Retention: either a plain maximum age ("30d") or a structured policy
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(untagged)]
+pub enum RetentionConfig {
+    MaxAge(String),
+    Policy(RetentionPolicy),
+}
+
+/// Grandfather-father-son retention. An archive is kept if any rule selects it;
+/// `max_age` keeps everything younger than the given duration.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct RetentionPolicy {
+    /// Newest backups that are never deleted (defaults to 1; 0 disables the floor)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_keep: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_last: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_hourly: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_daily: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_weekly: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_monthly: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_yearly: Option<usize>,
+    /// Delete the job's oldest backups while the storage exceeds this size (overrides the storage quota)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_total_size: Option<String>,
+}
+
+/// Periodic restore test of a backup into a scratch database
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct VerifyConfig {
+    /// Named restore target (from settings.restore_targets) hosting the scratch database
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// Inline connection overrides for the verification server
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server: Option<ConnectionOverride>,
+    /// Sanity queries run against the restored database (defaults to `SELECT 1`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub checks: Vec<VerifyCheck>,
+    /// When the scheduler should run the verification
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<ScheduleConfig>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct VerifyCheck {
+    pub query: String,
+    /// Minimum numeric value the first column of the first row must reach
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<i64>,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ScheduleConfig {
     pub cron: String,
+    /// What to do at startup about runs missed while the scheduler was down
+    #[serde(default)]
+    pub catch_up: CatchUp,
+    /// What to do when a scheduled time arrives while the previous run is still going
+    #[serde(default)]
+    pub overlap: Overlap,
+}
+
+/// Catch-up policy for scheduled runs, checked when the scheduler (re)starts a job
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CatchUp {
+    /// Wait for the next scheduled time
+    #[default]
+    Skip,
+    /// Run once immediately if a scheduled time passed since the last attempt
+    Missed,
+    /// Run once immediately if the last success is older than this (e.g. "36h")
+    #[serde(untagged)]
+    OlderThan(String),
+}
+
+/// Overlap policy for scheduled runs that outlast their cron interval
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Overlap {
+    /// Log and drop the tick
+    #[default]
+    Skip,
+    /// Run once more as soon as the current run finishes
+    Queue,
+    /// Start another run alongside the current one
+    Allow,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -115,6 +350,10 @@ pub struct StorageConfig {
     pub access_key_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub secret_access_key: Option<String>,
+
+    /// Quota for everything under the path or prefix, e.g. "200GB"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_total_size: Option<String>,
 }
 
 impl Config {
@@ -124,6 +363,49 @@ impl Config {
         Ok(config)
     }
 
+    /// Directory for persistent state such as verification results
+    pub fn state_dir(&self) -> PathBuf {
+        self.settings
+            .as_ref()
+            .and_then(|s| s.state_dir.clone())
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_STATE_DIR))
+    }
+
+    /// Number of scheduled runs allowed at once in a concurrency group
+    pub fn group_limit(&self, group: &str) -> usize {
+        match self.settings.as_ref().and_then(|s| s.concurrency.as_ref()) {
+            Some(concurrency) => concurrency.groups.get(group).copied().unwrap_or(concurrency.per_group),
+            None => default_group_limit(),
+        }
+    }
+
+    /// Find a backup configuration by name
+    pub fn find_backup(&self, name: &str) -> anyhow::Result<&BackupConfig> {
+        self.backups
+            .iter()
+            .find(|b| b.name == name)
+            .ok_or_else(|| anyhow::anyhow!("Backup '{}' not found in configuration", name))
+    }
+
+    /// Resolve the binary used to restore archives for a driver, if configured
+    pub fn restore_binary(&self, driver: &dyn BackupDriver) -> Option<PathBuf> {
+        driver.restore_binary(self.settings.as_ref()?.binary.as_ref()?)
+    }
+
+    /// Resolve the SQL client used for administrative statements, if configured
+    pub fn sql_client_binary(&self, driver: &dyn BackupDriver) -> Option<PathBuf> {
+        driver.sql_client_binary(self.settings.as_ref()?.binary.as_ref()?)
+    }
+
+    /// Look up a named restore target from settings
+    pub fn find_restore_target(&self, name: &str) -> anyhow::Result<&ConnectionOverride> {
+        self.settings
+            .as_ref()
+            .and_then(|s| s.restore_targets.as_ref())
+            .and_then(|targets| targets.get(name))
+            .ok_or_else(|| anyhow::anyhow!("Restore target '{}' not found in settings", name))
+    }
+
     /// Resolve storage configuration for a backup
     pub fn get_storage_for_backup(&self, backup: &BackupConfig) -> anyhow::Result<StorageConfig> {
         match &backup.storage {
@@ -142,7 +424,7 @@ impl Config {
                         if let Some(filename_prefix) = &storage_ref.filename_prefix {
                             storage.filename_prefix = Some(filename_prefix.clone());
                         }
-                        return Ok(storage);
+                        Ok(storage)
                     } else {
                         anyhow::bail!("Storage '{}' not found in settings", storage_ref.r#ref);
                     }
@@ -0,0 +1,98 @@
+use crate::config::{BackupConfig, BinarySettings};
+use crate::mysql;
+use crate::postgres;
+use crate::restore::RestoreOptions;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Static description of a database driver, registered in [`DRIVERS`]
+pub struct DriverInfo {
+    /// Value of `driver:` in a backup job
+    pub name: &'static str,
+    pub display_name: &'static str,
+    /// Dump tool named in log and error messages
+    pub dump_tool: &'static str,
+    /// Extension of basic-mode archives; parallel archives are always `.dir.tar.gz`
+    pub extension: &'static str,
+    pub create: fn(BackupConfig) -> Box<dyn BackupDriver>,
+}
+
+/// Every supported database driver.
+///
+/// Adding a driver means implementing [`BackupDriver`] and listing its info here;
+/// the orchestration in `runner` (temp dir, compression, manifest, storage,
+/// retention) is shared.
+pub const DRIVERS: &[&DriverInfo] = &[&postgres::DRIVER, &mysql::DRIVER];
+
+/// Database-specific half of a backup job: how to dump, restore and query a server
+#[async_trait]
+pub trait BackupDriver: Send + Sync {
+    fn info(&self) -> &'static DriverInfo;
+
+    /// Check the connection settings before any work starts
+    fn validate_connection(&self) -> Result<()>;
+
+    /// Command that writes a basic-mode dump to stdout
+    fn dump_stream_command(&self) -> Command;
+
+    /// Command that writes a parallel-mode dump into `dir`
+    fn dump_directory_command(&self, dir: &Path) -> Command;
+
+    /// Best-effort lookup of the source server version for the manifest, using
+    /// the configured SQL client or else the one next to the dump tool
+    async fn server_version(&self, sql_client: Option<&Path>) -> Option<String>;
+
+    /// Restore a `<extension>` or `.dir.tar.gz` archive into `options.target`
+    async fn restore(&self, archive: &Path, options: &RestoreOptions) -> Result<()>;
+
+    /// Run a query against the target database and return its output
+    async fn run_query(&self, options: &RestoreOptions, sql: &str) -> Result<String>;
+
+    /// Drop the target database if it exists
+    async fn drop_database(&self, options: &RestoreOptions) -> Result<()>;
+
+    /// Configured binary used to restore archives
+    fn restore_binary(&self, binary: &BinarySettings) -> Option<PathBuf>;
+
+    /// Configured SQL client used for administrative statements
+    fn sql_client_binary(&self, binary: &BinarySettings) -> Option<PathBuf>;
+}
+
+/// Find a registered driver by its config name (case-insensitive)
+pub fn lookup(name: &str) -> Option<&'static DriverInfo> {
+    DRIVERS.iter().copied().find(|info| info.name.eq_ignore_ascii_case(name))
+}
+
+/// Build the driver for a backup job
+pub fn create_driver(backup: &BackupConfig) -> Result<Box<dyn BackupDriver>> {
+    let info = lookup(&backup.driver)
+        .ok_or_else(|| anyhow::anyhow!("Unsupported database driver: {}", backup.driver))?;
+    Ok((info.create)(backup.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backup_with_driver(driver: &str) -> BackupConfig {
+        serde_yaml::from_str(&format!(
+            "name: test\ndriver: {}\nconnection: {{host: localhost, port: 5432, username: u, password: p, database: db}}",
+            driver
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_driver_registry() {
+        let driver = create_driver(&backup_with_driver("postgresql")).unwrap();
+        assert_eq!(driver.info().display_name, "PostgreSQL");
+
+        let driver = create_driver(&backup_with_driver("MySQL")).unwrap();
+        assert_eq!(driver.info().display_name, "MySQL");
+
+        assert!(create_driver(&backup_with_driver("oracle")).is_err());
+        assert_eq!(lookup("mysql").unwrap().extension, ".sql.gz");
+    }
+}
@@ -1,7 +1,30 @@
 use anyhow::{Context, Result};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tracing::warn;
 
+/// Bytes available to unprivileged users on the filesystem holding `path`,
+/// or `None` on platforms where this is not known.
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> Result<Option<u64>> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .context(format!("Invalid path: {}", path.display()))?;
+    // SAFETY: statvfs only writes into the zeroed struct we pass it
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error())
+            .context(format!("Failed to query free space of {}", path.display()));
+    }
+    #[allow(clippy::unnecessary_cast)] // field widths differ between platforms
+    Ok(Some(stat.f_bavail as u64 * stat.f_frsize as u64))
+}
+
+#[cfg(not(unix))]
+pub fn available_space(_path: &Path) -> Result<Option<u64>> {
+    Ok(None)
+}
+
 /// Move a file to the destination path.
 ///
 /// Falls back to copy+remove when source and destination are on different filesystems.
@@ -49,4 +72,15 @@ pub fn move_file_with_fallback(source: &Path, destination: &Path) -> Result<()>
             )
         }),
     }
-}
\ No newline at end of file
+}
+
+/// Resolve a companion binary (e.g. `psql` next to `pg_dump`).
+///
+/// Uses the same directory as `binary_path` when one is configured, otherwise
+/// relies on PATH lookup.
+pub fn sibling_binary(binary_path: Option<&Path>, name: &str) -> PathBuf {
+    match binary_path.and_then(|p| p.parent()) {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.join(name),
+        _ => PathBuf::from(name),
+    }
+}
@@ -0,0 +1,182 @@
+use crate::config::Config;
+use crate::runner::CompletedBackup;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use tracing::warn;
+
+const HISTORY_FILE: &str = "history.jsonl";
+
+/// What started a backup run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Trigger {
+    /// `dbackup backup`
+    Manual,
+    /// The `dbackup run` daemon
+    Scheduled,
+}
+
+/// One backup attempt, appended to `<state_dir>/history.jsonl`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub backup: String,
+    pub trigger: Trigger,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub duration_secs: f64,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+impl HistoryEntry {
+    /// Describe a finished run from the outcome of `runner::run_backup_job`
+    pub fn new(
+        backup: &str,
+        trigger: Trigger,
+        started_at: DateTime<Utc>,
+        result: &Result<CompletedBackup>,
+    ) -> Self {
+        let finished_at = Utc::now();
+        let (completed, error) = match result {
+            Ok(completed) => (Some(completed), None),
+            Err(e) => (None, Some(format!("{:#}", e))),
+        };
+        Self {
+            backup: backup.to_string(),
+            trigger,
+            started_at,
+            finished_at,
+            duration_secs: (finished_at - started_at).num_milliseconds() as f64 / 1000.0,
+            success: error.is_none(),
+            error,
+            location: completed.map(|c| c.location.clone()),
+            size: completed.map(|c| c.size),
+        }
+    }
+}
+
+/// Append a run to the history file in the state directory
+pub fn record(config: &Config, entry: &HistoryEntry) -> Result<()> {
+    let state_dir = config.state_dir();
+    std::fs::create_dir_all(&state_dir)
+        .context(format!("Failed to create state directory: {}", state_dir.display()))?;
+
+    let path = state_dir.join(HISTORY_FILE);
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .context(format!("Failed to open backup history: {}", path.display()))?;
+
+    // One write per line keeps concurrent appends from interleaving
+    let mut line = serde_json::to_string(entry).context("Failed to serialize history entry")?;
+    line.push('\n');
+    file.write_all(line.as_bytes()).context("Failed to write history entry")?;
+    Ok(())
+}
+
+/// Read the whole history, oldest first; a missing file is an empty history
+pub fn read(config: &Config) -> Result<Vec<HistoryEntry>> {
+    let path = config.state_dir().join(HISTORY_FILE);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context(format!("Failed to read backup history: {}", path.display())),
+    };
+
+    let mut entries = Vec::new();
+    for (number, line) in content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            // A crash can leave a partial last line; keep the rest readable
+            Err(e) => warn!("Skipping invalid entry on line {} of {}: {}", number + 1, path.display(), e),
+        }
+    }
+    Ok(entries)
+}
+
+/// Last successful and last failed run of one job
+#[derive(Debug, Default)]
+pub struct JobStatus<'a> {
+    pub last_success: Option<&'a HistoryEntry>,
+    pub last_failure: Option<&'a HistoryEntry>,
+}
+
+/// Latest outcomes of `backup` in a history read with [`read`]
+pub fn job_status<'a>(entries: &'a [HistoryEntry], backup: &str) -> JobStatus<'a> {
+    let mut status = JobStatus::default();
+    for entry in entries.iter().filter(|entry| entry.backup == backup) {
+        let slot = if entry.success { &mut status.last_success } else { &mut status.last_failure };
+        if slot.is_none_or(|latest| entry.started_at >= latest.started_at) {
+            *slot = Some(entry);
+        }
+    }
+    status
+}
+
+/// Average duration in seconds of the last few successful runs of `backup`
+pub fn typical_duration(entries: &[HistoryEntry], backup: &str) -> Option<f64> {
+    const RECENT_RUNS: usize = 5;
+    let mut successes: Vec<_> = entries.iter().filter(|entry| entry.backup == backup && entry.success).collect();
+    successes.sort_by_key(|entry| entry.started_at);
+    let recent = &successes[successes.len().saturating_sub(RECENT_RUNS)..];
+    (!recent.is_empty()).then(|| recent.iter().map(|entry| entry.duration_secs).sum::<f64>() / recent.len() as f64)
+}
+
+/// Render seconds as e.g. `42s`, `3m 05s` or `1h 12m`
+pub fn format_duration(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_history_round_trip() {
+        let dir = std::env::temp_dir().join(format!("dbackup_test_{}", uuid::Uuid::new_v4()));
+        let config: Config = serde_yaml::from_str(&format!("settings: {{state_dir: {}}}\nbackups: []", dir.display())).unwrap();
+        assert!(read(&config).unwrap().is_empty());
+
+        let started_at = |day| Utc.with_ymd_and_hms(2026, 2, day, 2, 0, 0).unwrap();
+        let completed = CompletedBackup { location: "/backups/app.dump.gz".to_string(), size: 2048 };
+        record(&config, &HistoryEntry::new("app", Trigger::Scheduled, started_at(16), &Ok(completed))).unwrap();
+        let failed = Err(anyhow::anyhow!("connection refused").context("Pre-flight check failed"));
+        record(&config, &HistoryEntry::new("app", Trigger::Manual, started_at(17), &failed)).unwrap();
+        record(&config, &HistoryEntry::new("shop", Trigger::Scheduled, started_at(17), &failed)).unwrap();
+
+        let entries = read(&config).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].size, Some(2048));
+        assert_eq!(entries[1].error.as_deref(), Some("Pre-flight check failed: connection refused"));
+
+        let status = job_status(&entries, "app");
+        assert_eq!(status.last_success.unwrap().started_at, started_at(16));
+        assert_eq!(status.last_failure.unwrap().trigger, Trigger::Manual);
+        assert!(job_status(&entries, "shop").last_success.is_none());
+        assert!(typical_duration(&entries, "app").is_some());
+        assert_eq!(typical_duration(&entries, "shop"), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(4.4), "4s");
+        assert_eq!(format_duration(185.0), "3m 05s");
+        assert_eq!(format_duration(4320.0), "1h 12m");
+    }
+}
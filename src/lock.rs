@@ -0,0 +1,483 @@
+use crate::config::{BackupConfig, Config};
+use crate::retention;
+use crate::storage::{self, StorageBackend};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Weak};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Lock files live in `<state_dir>/locks`
+const LOCK_DIR: &str = "locks";
+/// Lease objects live under this key in the job's S3 storage
+const LEASE_DIR: &str = ".dbackup-locks/";
+
+/// Jobs this process holds a lock on, keyed by lock file. Overlapping runs of a
+/// job in one process share its lock; the scheduler's overlap policy governs them.
+/// Each entry has its own mutex so taking one job's lock never waits on another's.
+static HELD: LazyLock<Mutex<HashMap<PathBuf, Arc<HeldEntry>>>> = LazyLock::new(Default::default);
+
+/// Registry slot for one job's lock
+type HeldEntry = Mutex<Weak<HeldLock>>;
+
+/// Who holds a job and the latest scheduled run claimed for it.
+///
+/// Stored in the lock file and, with `s3_lease`, in the lease object.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Claim {
+    /// `host:pid` of the instance that made the claim
+    owner: String,
+    /// Latest scheduled time claimed; manual runs keep the previous value
+    #[serde(skip_serializing_if = "Option::is_none")]
+    run: Option<DateTime<Utc>>,
+    acquired_at: DateTime<Utc>,
+    /// Leases only: when the claim lapses unless it is renewed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// Result of trying to claim a run of a job
+pub enum LockOutcome {
+    Acquired(JobLock),
+    /// Another instance has the job or the run; the reason is meant for the log
+    Busy(String),
+}
+
+/// Claim on a backup job, held until released or dropped
+pub struct JobLock {
+    held: Arc<HeldLock>,
+}
+
+impl JobLock {
+    /// Give up the claim. The last run of the job in this process lets the lease
+    /// lapse now instead of at its expiry.
+    pub async fn release(self) {
+        if let Some(held) = Arc::into_inner(self.held) {
+            if let Some(lease) = held.lease {
+                lease.release().await;
+            }
+        }
+    }
+}
+
+/// Locks this process holds on one job
+struct HeldLock {
+    /// Flocked for as long as the job runs here; closing the file releases it.
+    /// `None` if the state directory is not writable.
+    file: Option<Mutex<File>>,
+    path: PathBuf,
+    lease: Option<Lease>,
+}
+
+/// Claim a run of `backup` before executing it.
+///
+/// Takes an exclusive `flock` on `<state_dir>/locks/<job>.lock`, which keeps
+/// instances on the same host from running the job at once. With
+/// `settings.lock.s3_lease` and S3 storage, also holds a lease object in that
+/// storage, claimed with conditional writes, which does the same across hosts.
+/// `run` is the scheduled time being executed: a run at or before the latest
+/// claimed one is busy, so an instance that fires late does not repeat it.
+/// If the state directory is not writable the job runs without the host lock.
+pub async fn acquire(config: &Config, backup: &BackupConfig, run: Option<DateTime<Utc>>) -> Result<LockOutcome> {
+    let path = config.state_dir().join(LOCK_DIR).join(format!("{}.lock", lock_name(&backup.name)));
+
+    let entry = {
+        let mut registry = HELD.lock().await;
+        // Entries in use are shared outside the registry or locked by a claim in progress
+        registry.retain(|_, entry| {
+            Arc::strong_count(entry) > 1 || entry.try_lock().map_or(true, |held| held.strong_count() > 0)
+        });
+        registry.entry(path.clone()).or_default().clone()
+    };
+
+    let held = {
+        let mut entry = entry.lock().await;
+        match entry.upgrade() {
+            Some(held) => held,
+            None => {
+                let held = match HeldLock::acquire(config, backup, path, run).await? {
+                    Ok(held) => Arc::new(held),
+                    Err(reason) => return Ok(LockOutcome::Busy(reason)),
+                };
+                *entry = Arc::downgrade(&held);
+                held
+            }
+        }
+    };
+
+    let lock = JobLock { held };
+    match lock.held.claim(run).await? {
+        Some(reason) => {
+            // Do not keep a fresh lease for a run that is not ours
+            lock.release().await;
+            Ok(LockOutcome::Busy(reason))
+        }
+        None => Ok(LockOutcome::Acquired(lock)),
+    }
+}
+
+/// Lease duration if S3 leases are enabled
+pub fn lease_ttl(config: &Config) -> Result<Option<Duration>> {
+    let Some(lock) = config.settings.as_ref().and_then(|s| s.lock.as_ref()) else {
+        return Ok(None);
+    };
+    if !lock.s3_lease {
+        return Ok(None);
+    }
+    let ttl = retention::parse_duration(&lock.lease_ttl).context("Invalid settings.lock.lease_ttl")?;
+    if ttl.is_zero() {
+        anyhow::bail!("settings.lock.lease_ttl must be greater than 0");
+    }
+    Ok(Some(ttl))
+}
+
+impl HeldLock {
+    /// Take the flock and the lease; `Ok(Err(reason))` if another instance has either
+    async fn acquire(
+        config: &Config,
+        backup: &BackupConfig,
+        path: PathBuf,
+        run: Option<DateTime<Utc>>,
+    ) -> Result<Result<Self, String>> {
+        let locked = open_lock_file(&path).and_then(|file| match file.try_lock() {
+            Ok(()) => Ok(Some(file)),
+            Err(std::fs::TryLockError::WouldBlock) => Ok(None),
+            Err(std::fs::TryLockError::Error(e)) => Err(e).context(format!("Failed to lock {}", path.display())),
+        });
+        let file = match locked {
+            Ok(Some(file)) => Some(Mutex::new(file)),
+            Ok(None) => {
+                return Ok(Err(format!("another dbackup process on this host holds {}", path.display())));
+            }
+            Err(e) => {
+                // Losing the host lock must not cost the backup itself
+                warn!("Running backup '{}' without a host lock: {:#}", backup.name, e);
+                None
+            }
+        };
+
+        let lease = match lease_ttl(config)? {
+            Some(ttl) => match Lease::acquire(config, backup, run, ttl).await? {
+                Ok(lease) => lease,
+                Err(reason) => return Ok(Err(reason)),
+            },
+            None => None,
+        };
+        Ok(Ok(Self { file, path, lease }))
+    }
+
+    /// Record `run` as claimed, or say why it cannot be
+    async fn claim(&self, run: Option<DateTime<Utc>>) -> Result<Option<String>> {
+        let now = Utc::now();
+        let mut file = match &self.file {
+            Some(file) => Some(file.lock().await),
+            None => None,
+        };
+        let previous: Option<Claim> = match file.as_mut() {
+            Some(file) => {
+                let mut content = String::new();
+                file.rewind()
+                    .and_then(|_| file.read_to_string(&mut content))
+                    .context(format!("Failed to read lock file: {}", self.path.display()))?;
+                serde_json::from_str(&content).ok()
+            }
+            None => None,
+        };
+        if let Some(reason) = previous.as_ref().and_then(|claim| blocking_reason(claim, run, now)) {
+            return Ok(Some(reason));
+        }
+        let claim = Claim {
+            owner: owner(),
+            run: run.max(previous.and_then(|claim| claim.run)),
+            acquired_at: now,
+            expires_at: None,
+        };
+
+        if let Some(lease) = &self.lease {
+            if let Some(reason) = lease.claim(claim.run).await? {
+                return Ok(Some(reason));
+            }
+        }
+
+        if let Some(file) = file.as_mut() {
+            let json = serde_json::to_string(&claim).context("Failed to serialize lock claim")?;
+            file.set_len(0)
+                .and_then(|_| file.rewind())
+                .and_then(|_| file.write_all(json.as_bytes()))
+                .context(format!("Failed to write lock file: {}", self.path.display()))?;
+        }
+        Ok(None)
+    }
+}
+
+/// Open the lock file, creating it and its directory if needed
+fn open_lock_file(path: &Path) -> Result<File> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .context(format!("Failed to create lock directory: {}", dir.display()))?;
+    }
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .context(format!("Failed to open lock file: {}", path.display()))
+}
+
+/// Why an existing claim stops a new claim for `run`, if it does
+fn blocking_reason(existing: &Claim, run: Option<DateTime<Utc>>, now: DateTime<Utc>) -> Option<String> {
+    if let (Some(run), Some(claimed)) = (run, existing.run) {
+        if claimed >= run {
+            return Some(format!(
+                "the run scheduled for {} was already claimed by {}",
+                run.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+                existing.owner
+            ));
+        }
+    }
+    match existing.expires_at {
+        Some(expires_at) if expires_at > now && existing.owner != owner() => Some(format!(
+            "{} holds the lease until {}",
+            existing.owner,
+            expires_at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")
+        )),
+        _ => None,
+    }
+}
+
+/// Lease object in a job's S3 storage, renewed in the background while held
+struct Lease {
+    storage: Arc<dyn StorageBackend>,
+    key: String,
+    ttl: Duration,
+    /// Last written claim and its ETag, shared with the renewal task
+    state: Arc<Mutex<(Claim, String)>>,
+    renewal: JoinHandle<()>,
+}
+
+impl Lease {
+    /// Create the lease, or take over one that lapsed; `Ok(Err(reason))` if another
+    /// instance holds it. Jobs without S3 storage have no lease and only the host lock.
+    async fn acquire(
+        config: &Config,
+        backup: &BackupConfig,
+        run: Option<DateTime<Utc>>,
+        ttl: Duration,
+    ) -> Result<Result<Option<Self>, String>> {
+        let storage_config = config.get_storage_for_backup(backup)
+            .context(format!("Failed to resolve storage for backup '{}'", backup.name))?;
+        if !storage_config.driver.eq_ignore_ascii_case("s3") {
+            warn!(
+                "settings.lock.s3_lease is set, but backup '{}' uses {} storage: only the host lock applies",
+                backup.name, storage_config.driver
+            );
+            return Ok(Ok(None));
+        }
+        let storage: Arc<dyn StorageBackend> = storage::create_storage(&storage_config).await?.into();
+        let key = format!("{}{}.json", LEASE_DIR, lock_name(&backup.name));
+
+        let now = Utc::now();
+        let mut claim = Claim { owner: owner(), run, acquired_at: now, expires_at: Some(expiry(now, ttl)?) };
+        let mut etag = storage.put_if(&key, serde_json::to_vec(&claim)?, None).await?;
+        if etag.is_none() {
+            let current = storage.read_versioned(&key).await?;
+            let expected = match &current {
+                Some((body, current_etag)) => {
+                    if let Ok(existing) = serde_json::from_slice::<Claim>(body) {
+                        if let Some(reason) = blocking_reason(&existing, run, now) {
+                            return Ok(Err(reason));
+                        }
+                        claim.run = claim.run.max(existing.run);
+                    }
+                    Some(current_etag.as_str())
+                }
+                None => None,
+            };
+            etag = storage.put_if(&key, serde_json::to_vec(&claim)?, expected).await?;
+        }
+        let Some(etag) = etag else {
+            return Ok(Err("another instance claimed the lease at the same time".to_string()));
+        };
+        info!("Acquired lease {}", storage.location_of(&key));
+
+        let state = Arc::new(Mutex::new((claim, etag)));
+        let renewal = tokio::spawn(renew(storage.clone(), key.clone(), state.clone(), ttl));
+        Ok(Ok(Some(Self { storage, key, ttl, state, renewal })))
+    }
+
+    /// Extend the lease and record `run` in it; `Some(reason)` if it was lost
+    async fn claim(&self, run: Option<DateTime<Utc>>) -> Result<Option<String>> {
+        let mut state = self.state.lock().await;
+        let claim = Claim { run: run.max(state.0.run), expires_at: Some(expiry(Utc::now(), self.ttl)?), ..state.0.clone() };
+        match self.storage.put_if(&self.key, serde_json::to_vec(&claim)?, Some(&state.1)).await? {
+            Some(etag) => {
+                *state = (claim, etag);
+                Ok(None)
+            }
+            None => Ok(Some(format!("lost lease {} to another instance", self.key))),
+        }
+    }
+
+    /// Mark the lease expired, keeping the claimed run so late instances still skip it
+    async fn release(&self) {
+        self.renewal.abort();
+        let state = self.state.lock().await;
+        let claim = Claim { expires_at: Some(Utc::now()), ..state.0.clone() };
+        let body = match serde_json::to_vec(&claim) {
+            Ok(body) => body,
+            Err(e) => return warn!("Failed to serialize lease: {}", e),
+        };
+        match self.storage.put_if(&self.key, body, Some(&state.1)).await {
+            Ok(Some(_)) => {}
+            Ok(None) => warn!("Lease {} was taken over by another instance", self.key),
+            Err(e) => warn!("Failed to release lease {}: {:#}", self.key, e),
+        }
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        // A lease that is not released lapses at its expiry
+        self.renewal.abort();
+    }
+}
+
+/// Extend the lease every third of its duration for as long as it is held
+async fn renew(storage: Arc<dyn StorageBackend>, key: String, state: Arc<Mutex<(Claim, String)>>, ttl: Duration) {
+    loop {
+        tokio::time::sleep(ttl / 3).await;
+        let mut state = state.lock().await;
+        let Ok(expires_at) = expiry(Utc::now(), ttl) else { return };
+        let claim = Claim { expires_at: Some(expires_at), ..state.0.clone() };
+        let Ok(body) = serde_json::to_vec(&claim) else { return };
+        match storage.put_if(&key, body, Some(&state.1)).await {
+            Ok(Some(etag)) => *state = (claim, etag),
+            Ok(None) => return warn!("Lost lease {}: another instance took it over", key),
+            Err(e) => warn!("Failed to renew lease {}: {:#}", key, e),
+        }
+    }
+}
+
+fn expiry(now: DateTime<Utc>, ttl: Duration) -> Result<DateTime<Utc>> {
+    Ok(now + chrono::Duration::from_std(ttl).context("Lease duration is too long")?)
+}
+
+/// File-name-safe form of a job name
+fn lock_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// `host:pid` of this process
+fn owner() -> String {
+    format!("{}:{}", hostname(), std::process::id())
+}
+
+fn hostname() -> String {
+    #[cfg(unix)]
+    {
+        let mut buf = [0u8; 256];
+        // SAFETY: gethostname writes at most `buf.len()` bytes into the buffer
+        if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } == 0 {
+            let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+            return String::from_utf8_lossy(&buf[..end]).into_owned();
+        }
+    }
+    std::env::var("COMPUTERNAME").unwrap_or_else(|_| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn test_local_lock() {
+        let dir = std::env::temp_dir().join(format!("dbackup_test_{}", uuid::Uuid::new_v4()));
+        let config: Config = serde_yaml::from_str(&format!(
+            "settings: {{state_dir: {}}}\nbackups:\n  - name: Nightly app/db\n    driver: postgresql\n    \
+             connection: {{host: localhost, port: 5432, username: u, password: p, database: db}}\n    \
+             storage: {{driver: local, path: {}}}",
+            dir.display(),
+            dir.join("out").display()
+        ))
+        .unwrap();
+        let backup = &config.backups[0];
+        let at = |hour| Some(Utc.with_ymd_and_hms(2026, 2, 18, hour, 0, 0).unwrap());
+        let acquired = |outcome: LockOutcome| matches!(outcome, LockOutcome::Acquired(_));
+
+        let LockOutcome::Acquired(lock) = acquire(&config, backup, at(2)).await.unwrap() else {
+            panic!("first claim should succeed");
+        };
+        // Another process cannot take the flock while this one runs the job
+        let path = dir.join("locks/Nightly_app_db.lock");
+        assert!(File::open(&path).unwrap().try_lock().is_err());
+        // Overlapping runs in this process share the lock, but never repeat a run
+        assert!(!acquired(acquire(&config, backup, at(2)).await.unwrap()));
+        let LockOutcome::Acquired(overlapping) = acquire(&config, backup, at(3)).await.unwrap() else {
+            panic!("a later run should be claimable");
+        };
+        lock.release().await;
+        overlapping.release().await;
+        assert!(File::open(&path).unwrap().try_lock().is_ok());
+
+        // Claimed runs stay claimed after release; manual runs keep the marker
+        assert!(!acquired(acquire(&config, backup, at(3)).await.unwrap()));
+        assert!(acquired(acquire(&config, backup, None).await.unwrap()));
+        assert!(!acquired(acquire(&config, backup, at(3)).await.unwrap()));
+        assert!(acquired(acquire(&config, backup, at(4)).await.unwrap()));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unwritable_state_dir_runs_unlocked() {
+        // A file where the state directory should be: the lock directory cannot be created
+        let state_dir = std::env::temp_dir().join(format!("dbackup_test_{}", uuid::Uuid::new_v4()));
+        std::fs::write(&state_dir, b"").unwrap();
+        let config: Config = serde_yaml::from_str(&format!(
+            "settings: {{state_dir: {}}}\nbackups:\n  - name: app\n    driver: postgresql\n    \
+             connection: {{host: localhost, port: 5432, username: u, password: p, database: db}}\n    \
+             storage: {{driver: local, path: /tmp}}",
+            state_dir.display()
+        ))
+        .unwrap();
+
+        let outcome = acquire(&config, &config.backups[0], None).await.unwrap();
+        assert!(matches!(outcome, LockOutcome::Acquired(_)));
+
+        std::fs::remove_file(&state_dir).unwrap();
+    }
+
+    #[test]
+    fn test_blocking_reason() {
+        let now = Utc.with_ymd_and_hms(2026, 2, 18, 2, 0, 0).unwrap();
+        let lease = |owner: &str, run_hour, expires_in: i64| Claim {
+            owner: owner.to_string(),
+            run: Some(Utc.with_ymd_and_hms(2026, 2, 18, run_hour, 0, 0).unwrap()),
+            acquired_at: now,
+            expires_at: Some(now + chrono::Duration::seconds(expires_in)),
+        };
+        let other = "db-backup-2:4242";
+
+        // Lapsed lease of an earlier run: free
+        assert!(blocking_reason(&lease(other, 1, -60), Some(now), now).is_none());
+        // Same run claimed elsewhere, even if that instance finished
+        let reason = blocking_reason(&lease(other, 2, -60), Some(now), now).unwrap();
+        assert!(reason.contains("already claimed by db-backup-2:4242"));
+        // Earlier run still going elsewhere
+        assert!(blocking_reason(&lease(other, 1, 60), Some(now), now).unwrap().contains("holds the lease"));
+        assert!(blocking_reason(&lease(other, 1, 60), None, now).is_some());
+        // This process's own lease only blocks repeats
+        assert!(blocking_reason(&lease(&owner(), 1, 60), Some(now), now).is_none());
+    }
+}
@@ -6,17 +6,25 @@ mod updater;
 mod storage;
 mod retention;
 mod fs_utils;
+mod artifact;
+mod manifest;
+mod restore;
+mod verify;
+mod runner;
+mod driver;
+mod state;
+mod history;
+mod lock;
 
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
-use config::Config;
-use postgres::PostgresBackup;
-use mysql::MysqlBackup;
+use config::{BackupConfig, Config, ConnectionConfig, ConnectionOverride};
+use history::{HistoryEntry, Trigger};
 use scheduler::BackupScheduler;
+use restore::{RestoreOptions, RestoreWorkspace};
 use updater::check_and_show_update;
 use std::path::PathBuf;
 use tracing::{error, info};
-use tracing_subscriber;
 
 // Version information from build environment
 const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -29,7 +37,7 @@ const FALLBACK_CONFIG_PATH: &str = "backup.yml";
 #[command(name = "dbackup")]
 #[command(version = VERSION)]
 #[command(about = "A robust database backup utility")]
-#[command(long_about = "A robust database backup utility with multi-engine support, cloud storage integration, and automated scheduling.\n\nExamples:\n  dbackup backup -c /path/to/config.yml          # Run all backups\n  dbackup backup -c /path/to/config.yml -n pg1  # Run specific backup\n  dbackup validate -c /path/to/config.yml       # Validate configuration\n  dbackup run -c /path/to/config.yml            # Start scheduled backups\n  dbackup update                                 # Check and install updates\n  dbackup version                                # Show version and build info")]
+#[command(long_about = "A robust database backup utility with multi-engine support, cloud storage integration, and automated scheduling.\n\nExamples:\n  dbackup backup -c /path/to/config.yml          # Run all backups\n  dbackup backup -c /path/to/config.yml -n pg1  # Run specific backup\n  dbackup validate -c /path/to/config.yml       # Validate configuration\n  dbackup run -c /path/to/config.yml            # Start scheduled backups\n  dbackup restore -n pg1 -a backup_20260101_020000.dump.gz --target-database pg1_restore --create-database  # Restore into a new database\n  dbackup verify -c /path/to/config.yml -n pg1  # Test-restore the newest backup\n  dbackup list -c /path/to/config.yml           # List stored backups\n  dbackup prune -c /path/to/config.yml --dry-run  # Preview retention\n  dbackup update                                 # Check and install updates\n  dbackup version                                # Show version and build info")]
 struct Cli {
     /// Show version information
     #[arg(long, global = true)]
@@ -76,6 +84,136 @@ enum Commands {
         /// Maximum number of concurrent backup jobs (default: 2)
         #[arg(short, long, default_value = "2")]
         concurrency: usize,
+
+        /// Also reload the configuration when the file changes (SIGHUP always reloads)
+        #[arg(short, long)]
+        watch: bool,
+
+        /// How long to wait for running backups on SIGTERM/Ctrl+C before aborting them
+        #[arg(long, default_value = "2m")]
+        grace_period: String,
+    },
+    /// Restore a backup archive into its database
+    Restore {
+        /// Path to the configuration file (defaults to /etc/dbackup/backup.yml on Linux if available, otherwise backup.yml)
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// Name of the backup the archive belongs to
+        #[arg(short, long)]
+        name: String,
+
+        /// Archive to restore: a local file path, or a file name / key in the backup's storage
+        #[arg(short, long)]
+        archive: String,
+
+        /// Parallel restore jobs for directory archives (defaults to the backup's parallel_jobs)
+        #[arg(short, long)]
+        jobs: Option<u8>,
+
+        #[command(flatten)]
+        target: RestoreTargetArgs,
+    },
+    /// Verify backups by restoring them into a scratch database and running checks
+    Verify {
+        /// Path to the configuration file (defaults to /etc/dbackup/backup.yml on Linux if available, otherwise backup.yml)
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// Name of the backup to verify (if not specified, verifies all backups with a verify block)
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Archive to verify instead of the newest one (requires --name)
+        #[arg(short, long, requires = "name")]
+        archive: Option<String>,
+    },
+    /// Check an archive against its SHA-256 manifest
+    Check {
+        /// Path to the configuration file (only needed with --name)
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// Backup whose storage holds the archive (if not specified, the archive must be a local path)
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Archive to check: a local file path, or a file name / key in the backup's storage
+        archive: String,
+    },
+    /// List the backups available in every configured storage
+    List {
+        /// Path to the configuration file (defaults to /etc/dbackup/backup.yml on Linux if available, otherwise backup.yml)
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// Name of the backup to list (if not specified, lists all)
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Print machine-readable JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show recorded backup runs
+    History {
+        /// Path to the configuration file (defaults to /etc/dbackup/backup.yml on Linux if available, otherwise backup.yml)
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// Only show runs of this backup
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Only show runs started within this period (e.g. 24h, 7d)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Print machine-readable JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show the last success, last failure and next scheduled run of every backup
+    Status {
+        /// Path to the configuration file (defaults to /etc/dbackup/backup.yml on Linux if available, otherwise backup.yml)
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// Print machine-readable JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Preview the next runs of every schedule and where they overlap
+    Schedule {
+        /// Path to the configuration file (defaults to /etc/dbackup/backup.yml on Linux if available, otherwise backup.yml)
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// Number of upcoming runs to show per job
+        #[arg(long, default_value_t = 5)]
+        count: usize,
+
+        /// How far ahead the overlap timeline looks (e.g. 24h, 7d)
+        #[arg(long, default_value = "24h")]
+        timeline: String,
+
+        /// Print machine-readable JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Apply retention policies now, or preview them with --dry-run
+    Prune {
+        /// Path to the configuration file (defaults to /etc/dbackup/backup.yml on Linux if available, otherwise backup.yml)
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+
+        /// Name of the backup to prune (if not specified, prunes all with a retention policy)
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Print what would be deleted and why, without deleting anything
+        #[arg(long)]
+        dry_run: bool,
     },
     /// Show version and build information
     Version,
@@ -83,6 +221,58 @@ enum Commands {
     Update,
 }
 
+/// Where a restore should write to, layered over the backup's own connection
+#[derive(clap::Args)]
+struct RestoreTargetArgs {
+    /// Named restore target from settings.restore_targets
+    #[arg(long)]
+    target: Option<String>,
+
+    /// Override the target database host
+    #[arg(long)]
+    target_host: Option<String>,
+
+    /// Override the target database port
+    #[arg(long)]
+    target_port: Option<u16>,
+
+    /// Override the target database name
+    #[arg(long)]
+    target_database: Option<String>,
+
+    /// Create the target database if it does not exist
+    #[arg(long)]
+    create_database: bool,
+
+    /// Allow restoring over the backup's own source database
+    #[arg(long)]
+    force: bool,
+
+    /// Also replay MySQL's system schemas (mysql, sys), overwriting the target's users and grants
+    #[arg(long)]
+    include_system_databases: bool,
+}
+
+impl RestoreTargetArgs {
+    /// Resolve the restore target: source connection, then named target, then CLI flags
+    fn resolve(&self, config: &Config, source: &ConnectionConfig) -> Result<ConnectionConfig> {
+        let mut target = source.clone();
+
+        if let Some(name) = &self.target {
+            target = config.find_restore_target(name)?.apply(&target);
+        }
+
+        let cli_override = ConnectionOverride {
+            host: self.target_host.clone(),
+            port: self.target_port,
+            username: None,
+            password: None,
+            database: self.target_database.clone(),
+        };
+        Ok(cli_override.apply(&target))
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize tracing
@@ -104,27 +294,7 @@ async fn main() -> Result<()> {
 
     // Handle help flag
     if cli.help {
-        println!("A robust database backup utility with multi-engine support, cloud storage integration, and automated scheduling.\n");
-        println!("Usage: dbackup [COMMAND]\n");
-        println!("Commands:");
-        println!("  backup    Run a backup based on configuration file");
-        println!("  validate  Validate the configuration file");
-        println!("  generate  Generate a sample configuration file");
-        println!("  run       Run scheduled backups (listens for cron schedules)");
-        println!("  version   Show version and build information");
-        println!("  update    Check for and install the latest version");
-        println!("  help      Print this message or the help of the given subcommand(s)\n");
-        println!("Options:");
-        println!("  --version  Show version information");
-        println!("  --help     Show help information");
-        println!("  -h, --help Print help\n");
-        println!("Examples:");
-        println!("  dbackup backup -c /path/to/config.yml          # Run all backups");
-        println!("  dbackup backup -c /path/to/config.yml -n pg1  # Run specific backup");
-        println!("  dbackup validate -c /path/to/config.yml       # Validate configuration");
-        println!("  dbackup run -c /path/to/config.yml            # Start scheduled backups");
-        println!("  dbackup update                                 # Check and install updates");
-        println!("  dbackup version                                # Show version and build info\n");
+        print_usage();
         return Ok(());
     }
 
@@ -132,28 +302,7 @@ async fn main() -> Result<()> {
     let command = match cli.command {
         Some(cmd) => cmd,
         None => {
-            // Print help information
-            println!("A robust database backup utility with multi-engine support, cloud storage integration, and automated scheduling.\n");
-            println!("Usage: dbackup [COMMAND]\n");
-            println!("Commands:");
-            println!("  backup    Run a backup based on configuration file");
-            println!("  validate  Validate the configuration file");
-            println!("  generate  Generate a sample configuration file");
-            println!("  run       Run scheduled backups (listens for cron schedules)");
-            println!("  version   Show version and build information");
-            println!("  update    Check for and install the latest version");
-            println!("  help      Print this message or the help of the given subcommand(s)\n");
-            println!("Options:");
-            println!("  --version  Show version information");
-            println!("  --help     Show help information");
-            println!("  -h, --help Print help\n");
-            println!("Examples:");
-            println!("  dbackup backup -c /path/to/config.yml          # Run all backups");
-            println!("  dbackup backup -c /path/to/config.yml -n pg1  # Run specific backup");
-            println!("  dbackup validate -c /path/to/config.yml       # Validate configuration");
-            println!("  dbackup run -c /path/to/config.yml            # Start scheduled backups");
-            println!("  dbackup update                                 # Check and install updates");
-            println!("  dbackup version                                # Show version and build info\n");
+            print_usage();
             return Ok(());
         }
     };
@@ -170,9 +319,40 @@ async fn main() -> Result<()> {
         Commands::Generate { output } => {
             generate_sample_config(output)?;
         }
-        Commands::Run { config, concurrency } => {
+        Commands::Run { config, concurrency, watch, grace_period } => {
+            let config_path = resolve_config_path(config)?;
+            run_scheduled_backups(config_path, concurrency, watch, &grace_period).await?;
+        }
+        Commands::Restore { config, name, archive, jobs, target } => {
+            let config_path = resolve_config_path(config)?;
+            run_restore(config_path, name, archive, jobs, target).await?;
+        }
+        Commands::Verify { config, name, archive } => {
+            let config_path = resolve_config_path(config)?;
+            run_verify(config_path, name, archive).await?;
+        }
+        Commands::Check { config, name, archive } => {
+            run_check(config, name, archive).await?;
+        }
+        Commands::List { config, name, json } => {
+            let config_path = resolve_config_path(config)?;
+            run_list(config_path, name, json).await?;
+        }
+        Commands::History { config, name, since, json } => {
+            let config_path = resolve_config_path(config)?;
+            run_history(config_path, name, since, json)?;
+        }
+        Commands::Status { config, json } => {
+            let config_path = resolve_config_path(config)?;
+            run_status(config_path, json)?;
+        }
+        Commands::Schedule { config, count, timeline, json } => {
+            let config_path = resolve_config_path(config)?;
+            run_schedule_preview(config_path, count, timeline, json)?;
+        }
+        Commands::Prune { config, name, dry_run } => {
             let config_path = resolve_config_path(config)?;
-            run_scheduled_backups(config_path, concurrency).await?;
+            run_prune(config_path, name, dry_run).await?;
         }
         Commands::Version => {
             show_version(git_version).await;
@@ -185,6 +365,45 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+fn print_usage() {
+    println!("A robust database backup utility with multi-engine support, cloud storage integration, and automated scheduling.\n");
+    println!("Usage: dbackup [COMMAND]\n");
+    println!("Commands:");
+    println!("  backup    Run a backup based on configuration file");
+    println!("  validate  Validate the configuration file");
+    println!("  generate  Generate a sample configuration file");
+    println!("  run       Run scheduled backups (listens for cron schedules)");
+    println!("  restore   Restore a backup archive into its database");
+    println!("  verify    Test-restore backups into a scratch database");
+    println!("  check     Check an archive against its SHA-256 manifest");
+    println!("  list      List the backups available in every configured storage");
+    println!("  history   Show recorded backup runs");
+    println!("  status    Show the last success, last failure and next run of every backup");
+    println!("  schedule  Preview the next runs of every schedule and where they overlap");
+    println!("  prune     Apply retention policies now, or preview them with --dry-run");
+    println!("  version   Show version and build information");
+    println!("  update    Check for and install the latest version");
+    println!("  help      Print this message or the help of the given subcommand(s)\n");
+    println!("Options:");
+    println!("  --version  Show version information");
+    println!("  --help     Show help information");
+    println!("  -h, --help Print help\n");
+    println!("Examples:");
+    println!("  dbackup backup -c /path/to/config.yml          # Run all backups");
+    println!("  dbackup backup -c /path/to/config.yml -n pg1  # Run specific backup");
+    println!("  dbackup validate -c /path/to/config.yml       # Validate configuration");
+    println!("  dbackup run -c /path/to/config.yml            # Start scheduled backups");
+    println!("  dbackup restore -n pg1 -a backup_20260101_020000.dump.gz --target-database pg1_restore --create-database  # Restore into a new database");
+    println!("  dbackup verify -c /path/to/config.yml -n pg1  # Test-restore the newest backup");
+    println!("  dbackup list -c /path/to/config.yml           # List stored backups");
+    println!("  dbackup history -n pg1 --since 7d              # Runs of pg1 in the last week");
+    println!("  dbackup status -c /path/to/config.yml         # Did the last backups succeed?");
+    println!("  dbackup schedule --count 3 --timeline 7d      # Check cron expressions and overlaps");
+    println!("  dbackup prune -c /path/to/config.yml --dry-run  # Preview retention");
+    println!("  dbackup update                                 # Check and install updates");
+    println!("  dbackup version                                # Show version and build info\n");
+}
+
 fn resolve_config_path(explicit_config: Option<PathBuf>) -> Result<PathBuf> {
     // If config is explicitly provided via -c flag, use it (forced)
     if let Some(config) = explicit_config {
@@ -238,52 +457,30 @@ async fn run_backup(config_path: PathBuf, backup_name: Option<String>) -> Result
     info!("Running {} backup(s)", backups_to_run.len());
 
     for backup_config in backups_to_run {
-        match backup_config.driver.to_lowercase().as_str() {
-            "postgresql" => {
-                // Resolve storage configuration
-                let storage_config = config.get_storage_for_backup(&backup_config)
-                    .context(format!("Failed to resolve storage for backup '{}'", backup_config.name))?;
-
-                let backup = PostgresBackup::new(backup_config.clone(), storage_config);
-                
-                // Validate connection before attempting backup
-                backup.validate_connection()
-                    .context("Connection validation failed")?;
-
-                match backup.execute().await {
-                    Ok(location) => {
-                        info!("✓ Backup '{}' completed: {}", backup_config.name, location);
-                    }
-                    Err(e) => {
-                        error!("✗ Backup '{}' failed: {}", backup_config.name, e);
-                        return Err(e);
-                    }
-                }
+        // Never run alongside the same job started by a daemon or another shell
+        let lock = match lock::acquire(&config, &backup_config, None).await? {
+            lock::LockOutcome::Acquired(lock) => lock,
+            lock::LockOutcome::Busy(reason) => {
+                error!("✗ Backup '{}' is already running: {}", backup_config.name, reason);
+                anyhow::bail!("Backup '{}' is already running", backup_config.name);
             }
-            "mysql" => {
-                // Resolve storage configuration
-                let storage_config = config.get_storage_for_backup(&backup_config)
-                    .context(format!("Failed to resolve storage for backup '{}'", backup_config.name))?;
-
-                let backup = MysqlBackup::new(backup_config.clone(), storage_config);
-                
-                // Validate connection before attempting backup
-                backup.validate_connection()
-                    .context("Connection validation failed")?;
-
-                match backup.execute().await {
-                    Ok(location) => {
-                        info!("✓ Backup '{}' completed: {}", backup_config.name, location);
-                    }
-                    Err(e) => {
-                        error!("✗ Backup '{}' failed: {}", backup_config.name, e);
-                        return Err(e);
-                    }
-                }
+        };
+
+        let started_at = chrono::Utc::now();
+        let result = runner::run_backup_job(&config, &backup_config).await;
+        let entry = HistoryEntry::new(&backup_config.name, Trigger::Manual, started_at, &result);
+        if let Err(e) = history::record(&config, &entry) {
+            error!("Failed to record backup history: {}", e);
+        }
+        lock.release().await;
+
+        match result {
+            Ok(completed) => {
+                info!("✓ Backup '{}' completed: {}", backup_config.name, completed.location);
             }
-            driver => {
-                error!("Unsupported database driver: {}", driver);
-                anyhow::bail!("Unsupported driver: {}", driver);
+            Err(e) => {
+                error!("✗ Backup '{}' failed: {:#}", backup_config.name, e);
+                return Err(e);
             }
         }
     }
@@ -298,87 +495,519 @@ async fn validate_config(config_path: PathBuf) -> Result<()> {
     let config = Config::from_file(&config_path)
         .context("Failed to load configuration file")?;
 
+    lock::lease_ttl(&config)?;
+
     // Validate each backup configuration
     for backup_config in &config.backups {
         info!("Validating backup: {}", backup_config.name);
+        runner::validate_backup_job(&config, backup_config).await?;
+        info!("✓ Backup '{}' configuration is valid", backup_config.name);
+    }
+
+    info!("✓ Configuration is valid");
+    Ok(())
+}
+
+async fn run_restore(
+    config_path: PathBuf,
+    backup_name: String,
+    archive: String,
+    jobs: Option<u8>,
+    target_args: RestoreTargetArgs,
+) -> Result<()> {
+    info!("Loading configuration from: {}", config_path.display());
+    let config = Config::from_file(&config_path)
+        .context("Failed to load configuration file")?;
+
+    let backup_config = config.find_backup(&backup_name)?.clone();
+    let storage_config = config.get_storage_for_backup(&backup_config)
+        .context(format!("Failed to resolve storage for backup '{}'", backup_config.name))?;
+    let driver = driver::create_driver(&backup_config)?;
+
+    let target = target_args.resolve(&config, &backup_config.connection)?;
+    if backup_config.connection.database.is_empty() && !target.database.is_empty() {
+        anyhow::bail!(
+            "Backup '{}' dumps the whole server, so it cannot be restored as database '{}'. \
+             Restore it without a target database, onto another server with --target or --target-host",
+            backup_config.name,
+            target.database
+        );
+    }
+    if restore::is_same_database(&backup_config.connection, &target) {
+        let what = if target.database.is_empty() {
+            "every database".to_string()
+        } else {
+            format!("the source database '{}'", target.database)
+        };
+        if !target_args.force {
+            anyhow::bail!(
+                "Refusing to restore over {} on {}:{}. \
+                 Choose another target with --target, --target-host or --target-database, or pass --force",
+                what,
+                target.host,
+                target.port
+            );
+        }
+        info!("--force given: restoring over {}", what);
+    }
+
+    // Check the connection the restore will use before downloading the archive
+    let target_config = BackupConfig { connection: target.clone(), ..backup_config.clone() };
+    driver::create_driver(&target_config)?
+        .validate_connection()
+        .context(format!("Invalid restore target {}:{}", target.host, target.port))?;
+
+    let workspace = RestoreWorkspace::new()?;
+    let archive_path = restore::fetch_archive(&archive, &storage_config, &workspace).await?;
+
+    let options = RestoreOptions {
+        target,
+        jobs: jobs.unwrap_or(backup_config.parallel_jobs),
+        binary_path: config.restore_binary(driver.as_ref()),
+        sql_client_path: config.sql_client_binary(driver.as_ref()),
+        create_database: target_args.create_database,
+        include_system_databases: target_args.include_system_databases,
+    };
+
+    driver.restore(&archive_path, &options).await?;
+
+    info!(
+        "✓ Restored '{}' from {} into '{}' on {}:{}",
+        backup_config.name,
+        archive_path.display(),
+        options.target.database,
+        options.target.host,
+        options.target.port
+    );
+    Ok(())
+}
 
-        match backup_config.driver.to_lowercase().as_str() {
-            "postgresql" => {
-                // Resolve storage configuration
-                let storage_config = config.get_storage_for_backup(backup_config)
-                    .context(format!("Failed to resolve storage for backup '{}'", backup_config.name))?;
-
-                // Validate storage connection
-                info!("  Testing {} storage connection...", storage_config.driver);
-                match storage_config.driver.to_lowercase().as_str() {
-                    "s3" => {
-                        storage::S3Storage::new(&storage_config)
-                            .await
-                            .context(format!("S3 storage validation failed for backup '{}'", backup_config.name))?;
-                        info!("  ✓ S3 storage connection validated");
-                    }
-                    "local" => {
-                        storage::LocalStorage::new(&storage_config)
-                            .context(format!("Local storage validation failed for backup '{}'", backup_config.name))?;
-                        info!("  ✓ Local storage validated");
-                    }
-                    driver => {
-                        error!("Unsupported storage driver: {}", driver);
-                        anyhow::bail!("Unsupported storage driver: {}", driver);
-                    }
-                }
-
-                // Validate database connection
-                let backup = PostgresBackup::new(backup_config.clone(), storage_config);
-                backup.validate_connection()
-                    .context(format!("Database validation failed for backup '{}'", backup_config.name))?;
-                info!("  ✓ PostgreSQL connection validated");
+async fn run_verify(
+    config_path: PathBuf,
+    backup_name: Option<String>,
+    archive: Option<String>,
+) -> Result<()> {
+    info!("Loading configuration from: {}", config_path.display());
+    let config = Config::from_file(&config_path)
+        .context("Failed to load configuration file")?;
+
+    let backups_to_verify: Vec<_> = match &backup_name {
+        Some(name) => vec![config.find_backup(name)?.clone()],
+        None => config.backups.iter().filter(|b| b.verify.is_some()).cloned().collect(),
+    };
+
+    if backups_to_verify.is_empty() {
+        anyhow::bail!("No backups have a verify block configured; pass --name to verify one explicitly");
+    }
+
+    let mut failed = 0;
+    for backup_config in &backups_to_verify {
+        let report = verify::verify_backup(&config, backup_config, archive.as_deref()).await?;
+
+        if let Err(e) = verify::record(&config, &report) {
+            error!("Failed to record verification result: {}", e);
+        }
+
+        if report.success {
+            info!("✓ Verification of '{}' passed ({})", report.backup, report.archive);
+        } else {
+            error!(
+                "✗ Verification of '{}' failed ({}): {}",
+                report.backup,
+                report.archive,
+                report.error.as_deref().unwrap_or("unknown error")
+            );
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{} of {} verification(s) failed", failed, backups_to_verify.len());
+    }
+
+    info!("✓ All verifications passed");
+    Ok(())
+}
+
+async fn run_check(config: Option<PathBuf>, backup_name: Option<String>, archive: String) -> Result<()> {
+    let workspace = RestoreWorkspace::new()?;
+    let manifest_ref = manifest::manifest_name(&archive);
+
+    let (archive_path, manifest_path) = match backup_name {
+        Some(name) => {
+            let config_path = resolve_config_path(config)?;
+            let config = Config::from_file(&config_path)
+                .context("Failed to load configuration file")?;
+            let backup_config = config.find_backup(&name)?;
+            let storage_config = config.get_storage_for_backup(backup_config)
+                .context(format!("Failed to resolve storage for backup '{}'", backup_config.name))?;
+
+            let manifest_path = restore::fetch_archive(&manifest_ref, &storage_config, &workspace)
+                .await
+                .context("Manifest not found; was this archive written before checksums were recorded?")?;
+            let archive_path = restore::fetch_archive(&archive, &storage_config, &workspace).await?;
+            (archive_path, manifest_path)
+        }
+        None => (PathBuf::from(&archive), PathBuf::from(&manifest_ref)),
+    };
+
+    let manifest = manifest::BackupManifest::from_file(&manifest_path)?;
+    info!("Hashing {}...", archive_path.display());
+    let digest = manifest::digest_file(&archive_path)?;
+
+    match manifest.check(&digest) {
+        Ok(()) => {
+            info!("✓ {} OK (sha256 {}, {} bytes)", manifest.archive, digest.sha256, digest.size);
+            Ok(())
+        }
+        Err(e) => {
+            error!("✗ {}", e);
+            Err(e)
+        }
+    }
+}
+
+async fn run_list(config_path: PathBuf, backup_name: Option<String>, json: bool) -> Result<()> {
+    #[derive(serde::Serialize)]
+    struct ListEntry<'a> {
+        name: &'a str,
+        #[serde(flatten)]
+        stored: artifact::StoredArtifact,
+    }
+
+    let config = Config::from_file(&config_path)
+        .context("Failed to load configuration file")?;
+
+    let backups: Vec<_> = match &backup_name {
+        Some(name) => vec![config.find_backup(name)?],
+        None => config.backups.iter().collect(),
+    };
+
+    let mut entries = Vec::new();
+    for backup_config in backups {
+        let storage_config = config.get_storage_for_backup(backup_config)
+            .context(format!("Failed to resolve storage for backup '{}'", backup_config.name))?;
+        let artifacts = artifact::list_artifacts(backup_config, &storage_config)
+            .await
+            .context(format!("Failed to list backups for '{}'", backup_config.name))?;
+        entries.extend(artifacts.into_iter().map(|stored| ListEntry {
+            name: &backup_config.name,
+            stored,
+        }));
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        println!("No backups found");
+        return Ok(());
+    }
+
+    let name_width = entries.iter().map(|e| e.name.len()).max().unwrap_or(4).max(4);
+    println!("{:<name_width$}  {:<19}  {:>10}  {:<8}  LOCATION", "NAME", "TIMESTAMP", "SIZE", "MODE");
+    for entry in &entries {
+        let mode = match entry.stored.artifact.mode {
+            config::BackupMode::Basic => "basic",
+            config::BackupMode::Parallel => "parallel",
+        };
+        println!(
+            "{:<name_width$}  {:<19}  {:>10}  {:<8}  {}",
+            entry.name,
+            entry.stored.artifact.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            artifact::format_size(entry.stored.size),
+            mode,
+            entry.stored.location
+        );
+    }
+
+    Ok(())
+}
+
+fn run_history(config_path: PathBuf, backup_name: Option<String>, since: Option<String>, json: bool) -> Result<()> {
+    let config = Config::from_file(&config_path)
+        .context("Failed to load configuration file")?;
+
+    let cutoff = match &since {
+        Some(since) => {
+            let period = retention::parse_duration(since).context("Invalid --since")?;
+            Some(chrono::Utc::now() - chrono::Duration::from_std(period)?)
+        }
+        None => None,
+    };
+
+    let entries: Vec<_> = history::read(&config)?
+        .into_iter()
+        .filter(|entry| backup_name.as_ref().is_none_or(|name| &entry.backup == name))
+        .filter(|entry| cutoff.is_none_or(|cutoff| entry.started_at >= cutoff))
+        .collect();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        println!("No backup runs recorded");
+        return Ok(());
+    }
+
+    let name_width = entries.iter().map(|e| e.backup.len()).max().unwrap_or(4).max(4);
+    println!(
+        "{:<19}  {:<name_width$}  {:<9}  {:<6}  {:>8}  {:>10}  DETAILS",
+        "STARTED", "NAME", "TRIGGER", "RESULT", "DURATION", "SIZE"
+    );
+    for entry in &entries {
+        let trigger = match entry.trigger {
+            Trigger::Manual => "manual",
+            Trigger::Scheduled => "scheduled",
+        };
+        let details = match (&entry.location, &entry.error) {
+            (_, Some(error)) => error.as_str(),
+            (Some(location), None) => location.as_str(),
+            (None, None) => "",
+        };
+        println!(
+            "{:<19}  {:<name_width$}  {:<9}  {:<6}  {:>8}  {:>10}  {}",
+            entry.started_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S"),
+            entry.backup,
+            trigger,
+            if entry.success { "ok" } else { "failed" },
+            history::format_duration(entry.duration_secs),
+            entry.size.map(artifact::format_size).unwrap_or_else(|| "-".to_string()),
+            details
+        );
+    }
+
+    Ok(())
+}
+
+fn run_status(config_path: PathBuf, json: bool) -> Result<()> {
+    #[derive(serde::Serialize)]
+    struct StatusEntry<'a> {
+        name: &'a str,
+        last_success: Option<&'a HistoryEntry>,
+        last_failure: Option<&'a HistoryEntry>,
+        next_run: Option<chrono::DateTime<chrono::Local>>,
+    }
+
+    let config = Config::from_file(&config_path)
+        .context("Failed to load configuration file")?;
+    let entries = history::read(&config)?;
+    let now = chrono::Local::now();
+
+    let mut statuses = Vec::new();
+    for backup_config in &config.backups {
+        let status = history::job_status(&entries, &backup_config.name);
+        let next_run = match &backup_config.schedule {
+            Some(schedule) => scheduler::next_run(&schedule.cron, now)
+                .context(format!("Invalid schedule for backup '{}'", backup_config.name))?,
+            None => None,
+        };
+        statuses.push(StatusEntry {
+            name: &backup_config.name,
+            last_success: status.last_success,
+            last_failure: status.last_failure,
+            next_run,
+        });
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&statuses)?);
+        return Ok(());
+    }
+
+    if statuses.is_empty() {
+        println!("No backups configured");
+        return Ok(());
+    }
+
+    let format_run = |entry: Option<&HistoryEntry>| {
+        entry.map_or_else(
+            || "never".to_string(),
+            |entry| entry.started_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string(),
+        )
+    };
+    let name_width = statuses.iter().map(|s| s.name.len()).max().unwrap_or(4).max(4);
+    println!("{:<name_width$}  {:<19}  {:<19}  {:<19}  LAST ERROR", "NAME", "LAST SUCCESS", "LAST FAILURE", "NEXT RUN");
+    for status in &statuses {
+        // Only show an error that is newer than the last success
+        let last_error = match (status.last_failure, status.last_success) {
+            (Some(failure), Some(success)) if failure.started_at < success.started_at => "",
+            (Some(failure), _) => failure.error.as_deref().unwrap_or(""),
+            (None, _) => "",
+        };
+        println!(
+            "{:<name_width$}  {:<19}  {:<19}  {:<19}  {}",
+            status.name,
+            format_run(status.last_success),
+            format_run(status.last_failure),
+            status.next_run.map_or_else(|| "not scheduled".to_string(), |next| next.format("%Y-%m-%d %H:%M:%S").to_string()),
+            last_error
+        );
+    }
+
+    Ok(())
+}
+
+fn run_schedule_preview(config_path: PathBuf, count: usize, window: String, json: bool) -> Result<()> {
+    #[derive(serde::Serialize)]
+    struct Preview<'a> {
+        jobs: &'a [scheduler::UpcomingRuns],
+        timeline: &'a [scheduler::TimelineRun],
+    }
+
+    let config = Config::from_file(&config_path)
+        .context("Failed to load configuration file")?;
+    let period = retention::parse_duration(&window).context("Invalid --timeline")?;
+    let now = chrono::Local::now();
+
+    let jobs = scheduler::upcoming_runs(&config, count, now)?;
+    let entries = history::read(&config)?;
+    let timeline = scheduler::timeline(&jobs, now, now + chrono::Duration::from_std(period)?, |name, kind| {
+        // Only backup runs are recorded in the history
+        match kind {
+            state::RunKind::Backup => history::typical_duration(&entries, name),
+            state::RunKind::Verification => None,
+        }
+    });
+    let invalid = jobs.iter().filter(|job| job.error.is_some()).count();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&Preview { jobs: &jobs, timeline: &timeline })?);
+    } else if jobs.is_empty() {
+        println!("No scheduled backups or verifications");
+    } else {
+        let label = |name: &str, kind| format!("{} ({})", name, scheduler::describe(kind));
+        for job in &jobs {
+            println!("{}  cron \"{}\"  group {}", label(&job.name, job.kind), job.cron, job.group);
+            if let Some(error) = &job.error {
+                println!("  ✗ {}", error);
             }
-            "mysql" => {
-                // Resolve storage configuration
-                let storage_config = config.get_storage_for_backup(backup_config)
-                    .context(format!("Failed to resolve storage for backup '{}'", backup_config.name))?;
-
-                // Validate storage connection
-                info!("  Testing {} storage connection...", storage_config.driver);
-                match storage_config.driver.to_lowercase().as_str() {
-                    "s3" => {
-                        storage::S3Storage::new(&storage_config)
-                            .await
-                            .context(format!("S3 storage validation failed for backup '{}'", backup_config.name))?;
-                        info!("  ✓ S3 storage connection validated");
-                    }
-                    "local" => {
-                        storage::LocalStorage::new(&storage_config)
-                            .context(format!("Local storage validation failed for backup '{}'", backup_config.name))?;
-                        info!("  ✓ Local storage validated");
-                    }
-                    driver => {
-                        error!("Unsupported storage driver: {}", driver);
-                        anyhow::bail!("Unsupported storage driver: {}", driver);
-                    }
-                }
-
-                // Validate database connection
-                let backup = MysqlBackup::new(backup_config.clone(), storage_config);
-                backup.validate_connection()
-                    .context(format!("Database validation failed for backup '{}'", backup_config.name))?;
-                info!("  ✓ MySQL connection validated");
+            for time in &job.times {
+                println!(
+                    "  {}   {}",
+                    time.format("%Y-%m-%d %H:%M:%S %:z"),
+                    time.with_timezone(&chrono::Utc).format("%Y-%m-%d %H:%M:%S UTC")
+                );
+            }
+        }
+
+        println!("\nTimeline for the next {}:", window);
+        if timeline.is_empty() {
+            println!("  No runs");
+        } else {
+            let labels: Vec<_> = timeline.iter().map(|run| label(&run.name, run.kind)).collect();
+            let label_width = labels.iter().map(|l| l.len()).max().unwrap_or(3).max(3);
+            println!("{:<19}  {:>9}  {:<label_width$}  OVERLAPS", "STARTS", "EST. TIME", "JOB");
+            for (run, run_label) in timeline.iter().zip(&labels) {
+                let overlaps: Vec<_> = run
+                    .overlaps
+                    .iter()
+                    .map(|other| match other {
+                        _ if other.name == run.name && other.kind == run.kind => {
+                            format!("previous run from {}", other.start.format("%H:%M:%S"))
+                        }
+                        _ if other.same_group => format!("{}, same group {}", label(&other.name, other.kind), run.group),
+                        _ => label(&other.name, other.kind),
+                    })
+                    .collect();
+                println!(
+                    "{:<19}  {:>9}  {:<label_width$}  {}",
+                    run.start.format("%Y-%m-%d %H:%M:%S"),
+                    run.estimated_secs.map(history::format_duration).unwrap_or_else(|| "-".to_string()),
+                    run_label,
+                    overlaps.join("; ")
+                );
             }
-            driver => {
-                error!("Unsupported database driver: {}", driver);
-                anyhow::bail!("Unsupported driver: {}", driver);
+
+            let overlapping = timeline.iter().filter(|run| !run.overlaps.is_empty()).count();
+            if overlapping == 0 {
+                println!("\nNo overlapping runs");
+            } else {
+                println!(
+                    "\n{} run(s) overlap an earlier run; runs in the same group wait for a free slot",
+                    overlapping
+                );
             }
+            println!("Estimated times are the average of the last successful runs in the history");
         }
+    }
 
-        info!("✓ Backup '{}' configuration is valid", backup_config.name);
+    if invalid > 0 {
+        anyhow::bail!("{} schedule(s) have an invalid cron expression", invalid);
     }
+    Ok(())
+}
 
-    info!("✓ Configuration is valid");
+async fn run_prune(config_path: PathBuf, backup_name: Option<String>, dry_run: bool) -> Result<()> {
+    let config = Config::from_file(&config_path)
+        .context("Failed to load configuration file")?;
+
+    let backups: Vec<_> = match &backup_name {
+        Some(name) => vec![config.find_backup(name)?],
+        None => config.backups.iter().collect(),
+    };
+
+    let mut total_deleted = 0;
+    let mut total_freed = 0;
+    for backup_config in backups {
+        let storage_config = config.get_storage_for_backup(backup_config)
+            .context(format!("Failed to resolve storage for backup '{}'", backup_config.name))?;
+        let Some(rules) = retention::RetentionRules::for_backup(backup_config, &storage_config)
+            .context(format!("Invalid retention policy for backup '{}'", backup_config.name))? else {
+            info!("Backup '{}' has no retention policy, skipping", backup_config.name);
+            continue;
+        };
+        let storage = storage::create_storage(&storage_config).await?;
+
+        let decisions = retention::plan_retention(
+            storage.as_ref(),
+            backup_config,
+            artifact::filename_prefix(&storage_config),
+            &rules,
+            0,
+        )
+        .await
+        .context(format!("Failed to evaluate retention for '{}'", backup_config.name))?;
+
+        println!("{} ({})", backup_config.name, storage.get_location_display());
+        if decisions.is_empty() {
+            println!("  No backups found\n");
+            continue;
+        }
+        for decision in &decisions {
+            println!(
+                "  {:<6}  {:<19}  {:>10}  {}  ({})",
+                if decision.verdict.keep { "keep" } else { "delete" },
+                decision.created.format("%Y-%m-%d %H:%M:%S"),
+                artifact::format_size(decision.size),
+                decision.key,
+                decision.verdict.reason
+            );
+        }
+        println!();
+
+        let expired: Vec<_> = decisions.iter().filter(|d| !d.verdict.keep).collect();
+        total_freed += expired.iter().map(|d| d.size).sum::<u64>();
+        total_deleted += if dry_run {
+            expired.len()
+        } else {
+            retention::delete_expired(storage.as_ref(), &decisions).await
+        };
+    }
+
+    if dry_run {
+        println!("Dry run: would delete {} backup(s), freeing {}", total_deleted, artifact::format_size(total_freed));
+    } else {
+        info!("✓ Pruned {} backup(s)", total_deleted);
+    }
     Ok(())
 }
 
-async fn run_scheduled_backups(config_path: PathBuf, concurrency: usize) -> Result<()> {
+async fn run_scheduled_backups(config_path: PathBuf, concurrency: usize, watch: bool, grace_period: &str) -> Result<()> {
     info!("Loading configuration from: {}", config_path.display());
     let config = Config::from_file(&config_path)
         .context("Failed to load configuration file")?;
@@ -387,11 +1016,13 @@ async fn run_scheduled_backups(config_path: PathBuf, concurrency: usize) -> Resu
     if concurrency == 0 {
         anyhow::bail!("Concurrency must be greater than 0");
     }
+    let grace_period = retention::parse_duration(grace_period)
+        .context("Invalid --grace-period")?;
 
     // Create scheduler
-    let scheduler = BackupScheduler::new(config, concurrency);
+    let scheduler = BackupScheduler::new(config_path, config, concurrency, watch, grace_period);
 
-    // Run the scheduler (infinite loop until Ctrl+C)
+    // Run the scheduler until SIGTERM/Ctrl+C, reloading on SIGHUP
     scheduler.run().await
 }
 
@@ -404,6 +1035,9 @@ settings:
     # If not specified, the tool will use the binary from PATH
     pg_dump: /usr/bin/pg_dump
     mysqldump: /usr/bin/mysqldump
+    pg_restore: /usr/bin/pg_restore
+    mysql: /usr/bin/mysql
+    psql: /usr/bin/psql
   
   # Define reusable storage configurations
   storages:
@@ -432,6 +1066,13 @@ settings:
       access_key_id: minioadmin
       secret_access_key: minioadmin
 
+  # Named targets for `dbackup restore --target <name>`; unset fields fall back
+  # to the backup's own connection
+  restore_targets:
+    staging:
+      host: staging-db.example.com
+      database: production_db_restore
+
 backups:
   - name: "Production PostgreSQL - Local"
     driver: postgresql
@@ -444,10 +1085,19 @@ backups:
     mode: parallel
     parallel_jobs: 4
     schedule:
-      cron: "0 2 * * *"  # Daily at 2 AM
+      cron: "0 0 2 * * *"  # Daily at 2 AM
     # Reference centralized storage without custom prefix
     storage:
       ref: local_backup
+    # Optional: periodically restore the newest archive into a scratch database
+    verify:
+      target: staging
+      checks:
+        - query: "SELECT 1"
+        - query: "SELECT count(*) FROM users"
+          min: 1
+      schedule:
+        cron: "0 0 6 * * Sun"  # Sundays at 6 AM
 
   - name: "Production PostgreSQL - S3 AWS"
     driver: postgresql
@@ -460,7 +1110,7 @@ backups:
     mode: parallel
     parallel_jobs: 4
     schedule:
-      cron: "0 3 * * *"  # Daily at 3 AM
+      cron: "0 0 3 * * *"  # Daily at 3 AM
     # Reference centralized storage with custom S3 prefix override
     storage:
       ref: s3_aws
@@ -492,7 +1142,7 @@ backups:
     mode: parallel
     parallel_jobs: 4
     schedule:
-      cron: "0 2 * * *"  # Daily at 2 AM
+      cron: "0 0 2 * * *"  # Daily at 2 AM
     storage:
       ref: local_backup
       filename_prefix: mysql_
@@ -508,7 +1158,7 @@ backups:
     mode: parallel
     parallel_jobs: 4
     schedule:
-      cron: "0 4 * * *"  # Daily at 4 AM
+      cron: "0 0 4 * * *"  # Daily at 4 AM
     storage:
       ref: s3_aws
       prefix: prod-backups/mysql/
@@ -0,0 +1,229 @@
+use crate::config::{BackupConfig, BackupMode};
+use crate::storage::StorageBackend;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncReadExt;
+
+/// Suffix appended to an archive's file name to name its sidecar manifest
+pub const MANIFEST_SUFFIX: &str = ".manifest.json";
+
+/// Integrity and provenance record stored next to every backup archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub archive: String,
+    pub sha256: String,
+    pub size: u64,
+    pub backup: String,
+    pub driver: String,
+    pub database: String,
+    pub mode: BackupMode,
+    pub tool_version: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_version: Option<String>,
+}
+
+/// Digest and length of a written archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveDigest {
+    pub sha256: String,
+    pub size: u64,
+}
+
+impl BackupManifest {
+    pub fn new(
+        config: &BackupConfig,
+        archive: &str,
+        digest: ArchiveDigest,
+        started_at: DateTime<Utc>,
+        server_version: Option<String>,
+    ) -> Self {
+        Self {
+            archive: archive.to_string(),
+            sha256: digest.sha256,
+            size: digest.size,
+            backup: config.name.clone(),
+            driver: config.driver.clone(),
+            database: config.connection.database.clone(),
+            mode: config.mode.clone(),
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            started_at,
+            finished_at: Utc::now(),
+            server_version,
+        }
+    }
+
+    /// Write the manifest as `<archive><MANIFEST_SUFFIX>` in `dir`, returning its path
+    pub fn write_to_dir(&self, dir: &Path) -> Result<PathBuf> {
+        let path = dir.join(manifest_name(&self.archive));
+        let json = serde_json::to_string_pretty(self).context("Failed to serialize manifest")?;
+        std::fs::write(&path, json)
+            .context(format!("Failed to write manifest: {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// Compare a freshly computed digest against the recorded one
+    pub fn check(&self, actual: &ArchiveDigest) -> Result<()> {
+        if actual.size != self.size {
+            anyhow::bail!(
+                "Size mismatch for {}: manifest records {} bytes, archive has {} bytes",
+                self.archive,
+                self.size,
+                actual.size
+            );
+        }
+        if actual.sha256 != self.sha256 {
+            anyhow::bail!(
+                "Checksum mismatch for {}: manifest records {}, archive hashes to {}",
+                self.archive,
+                self.sha256,
+                actual.sha256
+            );
+        }
+        Ok(())
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .context(format!("Failed to read manifest: {}", path.display()))?;
+        serde_json::from_str(&content)
+            .context(format!("Invalid manifest: {}", path.display()))
+    }
+}
+
+/// Read a manifest stored under `key` in a storage backend
+pub async fn read_manifest(storage: &dyn StorageBackend, key: &str) -> Result<BackupManifest> {
+    let mut content = Vec::new();
+    storage.get(key).await?
+        .read_to_end(&mut content)
+        .await
+        .context(format!("Failed to read manifest: {}", storage.location_of(key)))?;
+    serde_json::from_slice(&content)
+        .context(format!("Invalid manifest: {}", storage.location_of(key)))
+}
+
+/// Name of the sidecar manifest for an archive
+pub fn manifest_name(archive: &str) -> String {
+    format!("{}{}", archive, MANIFEST_SUFFIX)
+}
+
+/// Writer adapter that hashes and counts every byte on its way to `inner`
+pub struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner, hasher: Sha256::new(), size: 0 }
+    }
+
+    /// Flush the inner writer and return it with the digest of everything written
+    pub fn finish(mut self) -> Result<(W, ArchiveDigest)> {
+        self.inner.flush().context("Failed to flush archive")?;
+        let digest = ArchiveDigest {
+            sha256: to_hex(&self.hasher.finalize()),
+            size: self.size,
+        };
+        Ok((self.inner, digest))
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Re-hash an archive on disk
+pub fn digest_file(path: &Path) -> Result<ArchiveDigest> {
+    let mut file = File::open(path)
+        .context(format!("Failed to open archive: {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut size = 0u64;
+
+    loop {
+        let n = file.read(&mut buffer).context("Failed to read archive")?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        size += n as u64;
+    }
+
+    Ok(ArchiveDigest { sha256: to_hex(&hasher.finalize()), size })
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashing_writer_matches_file_digest() {
+        let dir = std::env::temp_dir().join(format!("dbackup_test_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("archive.dump.gz");
+
+        let mut writer = HashingWriter::new(File::create(&path).unwrap());
+        writer.write_all(b"abc").unwrap();
+        let (_, digest) = writer.finish().unwrap();
+
+        assert_eq!(digest.size, 3);
+        assert_eq!(
+            digest.sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(digest_file(&path).unwrap(), digest);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_check_detects_mismatch() {
+        let digest = ArchiveDigest { sha256: "aa".repeat(32), size: 10 };
+        let manifest = BackupManifest {
+            archive: "backup_20260218_020000.dump.gz".to_string(),
+            sha256: digest.sha256.clone(),
+            size: digest.size,
+            backup: "test".to_string(),
+            driver: "postgresql".to_string(),
+            database: "testdb".to_string(),
+            mode: BackupMode::Basic,
+            tool_version: "0.0.0".to_string(),
+            started_at: Utc::now(),
+            finished_at: Utc::now(),
+            server_version: None,
+        };
+
+        assert!(manifest.check(&digest).is_ok());
+        assert!(manifest.check(&ArchiveDigest { size: 9, ..digest.clone() }).is_err());
+        assert!(manifest.check(&ArchiveDigest { sha256: "bb".repeat(32), ..digest }).is_err());
+    }
+
+    #[test]
+    fn test_manifest_name() {
+        assert_eq!(
+            manifest_name("backup_20260218_020000.dump.gz"),
+            "backup_20260218_020000.dump.gz.manifest.json"
+        );
+    }
+}
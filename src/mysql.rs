@@ -1,103 +1,46 @@
-use crate::config::{BackupConfig, StorageConfig};
-use crate::fs_utils::move_file_with_fallback;
-use crate::storage;
+use crate::config::{BackupConfig, BinarySettings, ConnectionConfig};
+use crate::driver::{BackupDriver, DriverInfo};
+use crate::fs_utils::sibling_binary;
+use crate::restore::{self, ArchiveFormat, RestoreOptions, RestoreWorkspace};
 use anyhow::{Context, Result};
-use chrono::Local;
-use flate2::write::GzEncoder;
-use flate2::Compression;
-use std::fs::File;
-use std::io::Write;
-use std::path::PathBuf;
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use tokio::process::Command;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
+
+pub const DRIVER: DriverInfo = DriverInfo {
+    name: "mysql",
+    display_name: "MySQL",
+    dump_tool: "mysqldump",
+    extension: ".sql.gz",
+    create: |config| Box::new(MysqlBackup::new(config)),
+};
 
 pub struct MysqlBackup {
     config: BackupConfig,
-    storage_config: StorageConfig,
 }
 
 impl MysqlBackup {
-    pub fn new(config: BackupConfig, storage_config: StorageConfig) -> Self {
-        Self { config, storage_config }
-    }
-
-    pub async fn execute(&self) -> Result<String> {
-        info!("Starting MySQL backup for: {}", self.config.name);
-
-        let timestamp = Local::now().format("%Y%m%d_%H%M%S");
-        
-        // Create storage backend
-        let storage_backend = storage::create_storage(&self.storage_config).await?;
-        
-        // For local storage, ensure the output directory exists
-        if self.storage_config.driver.to_lowercase() == "local" {
-            let path = self.storage_config.path.as_ref()
-                .context("Local storage requires 'path' configuration")?;
-            std::fs::create_dir_all(path)
-                .context("Failed to create output directory")?;
-        }
-
-        // Create a temporary directory for the backup file
-        let temp_dir = std::env::temp_dir().join(format!("dbackup_{}", uuid::Uuid::new_v4()));
-        std::fs::create_dir_all(&temp_dir)
-            .context("Failed to create temporary directory")?;
-
-        // Execute backup based on mode
-        let (temp_file_path, filename) = match self.config.mode {
-            crate::config::BackupMode::Basic => {
-                info!("Using basic mode (SQL format with compression)...");
-                self.dump_basic(&temp_dir, timestamp).await?
-            }
-            crate::config::BackupMode::Parallel => {
-                info!(
-                    "Using parallel mode (directory format with {} jobs)...",
-                    self.config.parallel_jobs
-                );
-                self.dump_parallel(&temp_dir, timestamp).await?
-            }
-        };
-
-        // Move to final location or upload to S3
-        let final_location = if self.storage_config.driver.to_lowercase() == "local" {
-            // For local storage, file is already in the correct location
-            temp_file_path.display().to_string()
-        } else {
-            // For remote storage (S3), upload the file
-            storage_backend.store(&temp_file_path, &filename).await?
-        };
-
-        // Cleanup temporary directory
-        let _ = std::fs::remove_dir_all(&temp_dir);
-
-        info!("Backup completed successfully: {}", final_location);
-        Ok(final_location)
+    pub fn new(config: BackupConfig) -> Self {
+        Self { config }
     }
 
-    async fn dump_basic(&self, temp_dir: &PathBuf, timestamp: impl std::fmt::Display) -> Result<(PathBuf, String)> {
+    /// mysqldump invocation shared by both backup modes
+    fn mysqldump(&self) -> Command {
         let conn = &self.config.connection;
-        let filename = format!(
-            "{}{}.sql.gz",
-            self.storage_config.filename_prefix.as_ref().unwrap_or(&"backup_".to_string()),
-            timestamp
-        );
-        let output_path = temp_dir.join(&filename);
-
-        info!("Backing up to: {}", output_path.display());
 
         // Determine mysqldump path
-        let mysqldump_path = self.config.binary_path.as_ref()
-            .map(|p| p.as_path())
-            .unwrap_or_else(|| std::path::Path::new("mysqldump"));
+        let mysqldump_path = self.config.binary_path.as_deref()
+            .unwrap_or_else(|| Path::new("mysqldump"));
 
-        // Build mysqldump command
         let mut cmd = Command::new(mysqldump_path);
 
         // Add connection parameters
         cmd.arg("--host").arg(&conn.host);
         cmd.arg("--port").arg(conn.port.to_string());
         cmd.arg("--user").arg(&conn.username);
-        cmd.arg(&format!("--password={}", &conn.password));
+        cmd.arg(format!("--password={}", &conn.password));
 
         // Backup options
         cmd.arg("--all-databases");
@@ -105,186 +48,382 @@ impl MysqlBackup {
         cmd.arg("--lock-tables=false");
         cmd.arg("--quick");
         cmd.arg("--verbose");
+        cmd
+    }
 
-        // Capture stdout and stderr
-        cmd.stdout(Stdio::piped());
-        cmd.stderr(Stdio::piped());
+    /// Create the target database unless it already exists
+    pub async fn create_database(&self, options: &RestoreOptions) -> Result<()> {
+        let target = &options.target;
+        if target.database.is_empty() {
+            warn!("No target database specified; skipping database creation");
+            return Ok(());
+        }
 
-        info!("Executing mysqldump with compression...");
-        let mut child = cmd.spawn().context("Failed to spawn mysqldump process")?;
+        info!("Ensuring target database '{}' exists", target.database);
+        run_mysql(
+            options.sql_client_path.as_deref(),
+            target,
+            None,
+            &format!("CREATE DATABASE IF NOT EXISTS {}", quote_identifier(&target.database)),
+        )
+        .await?;
+        Ok(())
+    }
+}
 
-        // Get stdout
-        let stdout = child
-            .stdout
-            .take()
-            .context("Failed to capture mysqldump stdout")?;
+#[async_trait]
+impl BackupDriver for MysqlBackup {
+    fn info(&self) -> &'static DriverInfo {
+        &DRIVER
+    }
 
-        // Create gzip compressed output file
-        let output_file = File::create(&output_path)
-            .context("Failed to create output file")?;
-        let mut encoder = GzEncoder::new(output_file, Compression::best());
+    fn validate_connection(&self) -> Result<()> {
+        let conn = &self.config.connection;
 
-        // Read from mysqldump and write compressed data
-        let mut reader = tokio::io::BufReader::new(stdout);
-        let mut buffer = Vec::new();
-        
-        tokio::io::copy(&mut reader, &mut buffer)
-            .await
-            .context("Failed to read mysqldump output")?;
-
-        encoder
-            .write_all(&buffer)
-            .context("Failed to write compressed data")?;
-        encoder.finish().context("Failed to finalize compression")?;
-
-        // Wait for the process to complete
-        let status = child.wait().await.context("mysqldump process failed")?;
-
-        if !status.success() {
-            let stderr = child.stderr.take();
-            if let Some(mut stderr) = stderr {
-                let mut error_output = String::new();
-                use tokio::io::AsyncReadExt;
-                stderr.read_to_string(&mut error_output).await?;
-                warn!("mysqldump stderr: {}", error_output);
-            }
-            anyhow::bail!("mysqldump failed with status: {}", status);
+        // Basic validation
+        if conn.host.is_empty() {
+            anyhow::bail!("Database host cannot be empty");
         }
 
-        if self.storage_config.driver.to_lowercase() == "local" {
-            // For local storage, move file to the final location
-            let final_path = self.storage_config.path.as_ref()
-                .context("Local storage requires 'path' configuration")?
-                .join(&filename);
-            move_file_with_fallback(&output_path, &final_path)
-                .context("Failed to move backup file to final location")?;
-            info!("Backup compressed to {}", final_path.display());
-            Ok((final_path, filename))
-        } else {
-            // For remote storage, keep in temp directory for upload
-            info!("Backup compressed to {}", output_path.display());
-            Ok((output_path, filename))
+        if conn.username.is_empty() {
+            anyhow::bail!("Database username cannot be empty");
         }
+
+        Ok(())
     }
 
-    async fn dump_parallel(&self, temp_dir: &PathBuf, timestamp: impl std::fmt::Display) -> Result<(PathBuf, String)> {
-        let conn = &self.config.connection;
-        let basename = format!("{}{}", self.storage_config.filename_prefix.as_ref().unwrap_or(&"backup_".to_string()), timestamp);
-        
-        // Create temporary directory for parallel backup files
-        let backup_tmp_dir = temp_dir.join(&basename);
-        std::fs::create_dir_all(&backup_tmp_dir)
-            .context("Failed to create temporary directory")?;
+    /// Restore a `.sql.gz` or `.dir.tar.gz` archive by streaming it into the mysql client.
+    ///
+    /// When the target database differs from the backed-up one, only the source
+    /// database's statements are replayed, renamed to the target.
+    async fn restore(&self, archive_path: &Path, options: &RestoreOptions) -> Result<()> {
+        let target = &options.target;
+        let source_database = &self.config.connection.database;
+        let format = ArchiveFormat::detect(archive_path)?;
+
+        let rename = if !source_database.is_empty()
+            && !target.database.is_empty()
+            && *source_database != target.database
+        {
+            info!(
+                "Restoring database '{}' from {} as '{}' on {}:{}",
+                source_database,
+                archive_path.display(),
+                target.database,
+                target.host,
+                target.port
+            );
+            Some(DatabaseRename::new(source_database, &target.database))
+        } else {
+            info!(
+                "Restoring {} into MySQL server {}:{}",
+                archive_path.display(),
+                target.host,
+                target.port
+            );
+            None
+        };
 
-        info!("Using temporary directory: {}", backup_tmp_dir.display());
+        if options.create_database {
+            self.create_database(options).await?;
+        }
 
-        // Determine mysqldump path
-        let mysqldump_path = self.config.binary_path.as_ref()
-            .map(|p| p.as_path())
-            .unwrap_or_else(|| std::path::Path::new("mysqldump"));
+        // Resolve the SQL stream inside the archive
+        let workspace = RestoreWorkspace::new()?;
+        let sql_path = match format {
+            ArchiveFormat::Compressed => archive_path.to_path_buf(),
+            ArchiveFormat::Directory => {
+                let dump_dir = workspace.path().join("dump");
+                restore::extract_tarball(archive_path, &dump_dir)?;
+                let sql_path = dump_dir.join("backup.sql");
+                if !sql_path.is_file() {
+                    anyhow::bail!("Archive does not contain backup.sql: {}", archive_path.display());
+                }
+                sql_path
+            }
+        };
 
-        // Build mysqldump command with parallel support options
-        let mut cmd = Command::new(mysqldump_path);
+        // Determine mysql client path
+        let mysql_path = options.binary_path.as_deref()
+            .unwrap_or_else(|| Path::new("mysql"));
+
+        let mut cmd = Command::new(mysql_path);
 
         // Add connection parameters
-        cmd.arg("--host").arg(&conn.host);
-        cmd.arg("--port").arg(conn.port.to_string());
-        cmd.arg("--user").arg(&conn.username);
-        cmd.arg(&format!("--password={}", &conn.password));
+        cmd.arg("--host").arg(&target.host);
+        cmd.arg("--port").arg(target.port.to_string());
+        cmd.arg("--user").arg(&target.username);
+        cmd.arg(format!("--password={}", &target.password));
 
-        // Backup options for parallel
-        cmd.arg("--all-databases");
-        cmd.arg("--single-transaction");
-        cmd.arg("--lock-tables=false");
-        cmd.arg("--quick");
-        cmd.arg("--verbose");
+        cmd.stdin(Stdio::piped());
+        cmd.stdout(Stdio::null());
+        cmd.stderr(Stdio::piped());
+        cmd.kill_on_drop(true);
 
-        // Output to file in the temporary directory
-        let output_file = backup_tmp_dir.join("backup.sql");
-        cmd.arg(&format!("--result-file={}", output_file.display()));
+        info!("Executing mysql client...");
+        let mut child = cmd.spawn().context("Failed to spawn mysql process")?;
+        let stderr = restore::collect_output(child.stderr.take());
+        let mut stdin = child.stdin.take().context("Failed to capture mysql stdin")?;
 
-        // Capture stderr for logging
-        cmd.stderr(Stdio::piped());
+        let reader = restore::open_dump(&sql_path)?;
+        let streamed = match rename {
+            Some(mut rename) => {
+                restore::stream_lines_into(reader, &mut stdin, move |line| rename.filter(line)).await
+            }
+            None if options.include_system_databases => restore::stream_lines_into(reader, &mut stdin, Some).await,
+            None => {
+                info!(
+                    "Skipping system databases ({}); pass --include-system-databases to restore them",
+                    SYSTEM_DATABASES.join(", ")
+                );
+                let mut skip = SkipSystemDatabases::default();
+                restore::stream_lines_into(reader, &mut stdin, move |line| skip.filter(line)).await
+            }
+        };
+        drop(stdin);
 
-        info!(
-            "Executing mysqldump for parallel-ready backup..."
-        );
-        
-        let mut child = cmd.spawn().context("Failed to spawn mysqldump process")?;
-
-        // Wait for the process to complete
-        let status = child.wait().await.context("mysqldump process failed")?;
-
-        if !status.success() {
-            let stderr = child.stderr.take();
-            if let Some(mut stderr) = stderr {
-                let mut error_output = String::new();
-                use tokio::io::AsyncReadExt;
-                stderr.read_to_string(&mut error_output).await?;
-                warn!("mysqldump stderr: {}", error_output);
+        restore::wait_for_client("mysql", child, stderr).await?;
+        let bytes = streamed?;
+
+        info!("✓ Restore completed ({} MiB of SQL replayed)", bytes / (1024 * 1024));
+        Ok(())
+    }
+
+    /// Best-effort lookup of the source server version for the manifest
+    async fn server_version(&self, sql_client: Option<&Path>) -> Option<String> {
+        let mysql_path = sql_client
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| sibling_binary(self.config.binary_path.as_deref(), "mysql"));
+        match run_mysql(Some(&mysql_path), &self.config.connection, None, "SELECT VERSION()").await {
+            Ok(version) => Some(version.trim().to_string()).filter(|v| !v.is_empty()),
+            Err(e) => {
+                debug!("Could not determine MySQL server version: {}", e);
+                None
             }
-            
-            // Cleanup temp directory on failure
-            let _ = std::fs::remove_dir_all(&backup_tmp_dir);
-            anyhow::bail!("mysqldump failed with status: {}", status);
         }
+    }
 
-        // Compress the directory into a tar.gz file
-        let tar_filename = format!("{}.dir.tar.gz", basename);
-        let tar_path = temp_dir.join(&tar_filename);
-
-        info!("Compressing backup to {}", tar_path.display());
-        self.compress_directory(&backup_tmp_dir, &tar_path).await?;
-
-        // Cleanup the backup directory
-        std::fs::remove_dir_all(&backup_tmp_dir)
-            .context("Failed to remove temporary directory")?;
-
-        if self.storage_config.driver.to_lowercase() == "local" {
-            // For local storage, move file to the final location
-            let final_path = self.storage_config.path.as_ref()
-                .context("Local storage requires 'path' configuration")?
-                .join(&tar_filename);
-            move_file_with_fallback(&tar_path, &final_path)
-                .context("Failed to move backup file to final location")?;
-            info!("Backup compressed to {}", final_path.display());
-            Ok((final_path, tar_filename))
-        } else {
-            // For remote storage, keep in temp directory for upload
-            info!("Backup compressed to {}", tar_path.display());
-            Ok((tar_path, tar_filename))
+    /// Drop the target database if it exists
+    async fn drop_database(&self, options: &RestoreOptions) -> Result<()> {
+        let target = &options.target;
+        info!("Dropping database '{}'", target.database);
+        run_mysql(
+            options.sql_client_path.as_deref(),
+            target,
+            None,
+            &format!("DROP DATABASE IF EXISTS {}", quote_identifier(&target.database)),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Run a query against the target database and return its output
+    async fn run_query(&self, options: &RestoreOptions, sql: &str) -> Result<String> {
+        run_mysql(
+            options.sql_client_path.as_deref(),
+            &options.target,
+            Some(&options.target.database),
+            sql,
+        )
+        .await
+    }
+
+    fn dump_stream_command(&self) -> Command {
+        self.mysqldump()
+    }
+
+    fn dump_directory_command(&self, dir: &Path) -> Command {
+        let mut cmd = self.mysqldump();
+
+        // Output to file in the dump directory
+        let output_file = dir.join("backup.sql");
+        cmd.arg(format!("--result-file={}", output_file.display()));
+        cmd
+    }
+
+    fn restore_binary(&self, binary: &BinarySettings) -> Option<PathBuf> {
+        binary.mysql.clone()
+    }
+
+    fn sql_client_binary(&self, binary: &BinarySettings) -> Option<PathBuf> {
+        binary.mysql.clone()
+    }
+}
+
+/// Run a single SQL statement with the mysql client and return its tab-separated output
+async fn run_mysql(
+    client: Option<&Path>,
+    connection: &ConnectionConfig,
+    database: Option<&str>,
+    sql: &str,
+) -> Result<String> {
+    let mysql_path = client.unwrap_or_else(|| Path::new("mysql"));
+
+    let mut cmd = Command::new(mysql_path);
+    cmd.arg("--host").arg(&connection.host);
+    cmd.arg("--port").arg(connection.port.to_string());
+    cmd.arg("--user").arg(&connection.username);
+    cmd.arg(format!("--password={}", &connection.password));
+    if let Some(database) = database {
+        cmd.arg("--database").arg(database);
+    }
+    cmd.arg("--batch");
+    cmd.arg("--skip-column-names");
+    cmd.arg("--execute").arg(sql);
+
+    let output = cmd.output().await.context("Failed to run mysql")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        warn!("mysql stderr: {}", stderr);
+        anyhow::bail!("mysql failed with status {}: {}", output.status, stderr.trim());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+/// MySQL's own schemas; replaying them overwrites the target server's users and grants
+const SYSTEM_DATABASES: &[&str] = &["mysql", "sys", "performance_schema", "information_schema"];
+
+/// First backquoted identifier on a dump line, e.g. the database of `USE \`app\`;`
+fn quoted_name(line: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(line);
+    let mut chars = text[text.find('`')? + 1..].chars().peekable();
+    let mut name = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '`' if chars.peek() == Some(&'`') => {
+                chars.next();
+                name.push('`');
+            }
+            '`' => return Some(name),
+            c => name.push(c),
         }
     }
+    None
+}
 
-    async fn compress_directory(&self, source_dir: &PathBuf, output_path: &PathBuf) -> Result<()> {
-        use tar::Builder;
+/// Drops the system schemas from an `--all-databases` dump, keeping every user database
+#[derive(Default)]
+struct SkipSystemDatabases {
+    skipping: bool,
+}
 
-        let tar_gz = File::create(output_path)
-            .context("Failed to create tar.gz file")?;
-        let encoder = GzEncoder::new(tar_gz, Compression::default());
-        let mut tar = Builder::new(encoder);
+impl SkipSystemDatabases {
+    fn filter(&mut self, line: Vec<u8>) -> Option<Vec<u8>> {
+        let is_system = |line: &[u8]| quoted_name(line).is_some_and(|name| SYSTEM_DATABASES.contains(&name.as_str()));
 
-        // Add all files from the directory to the tar archive
-        tar.append_dir_all(".", source_dir)
-            .context("Failed to add directory to tar archive")?;
+        if line.starts_with(b"CREATE DATABASE ") {
+            return (!is_system(&line)).then_some(line);
+        }
+        if line.starts_with(b"USE `") {
+            self.skipping = is_system(&line);
+        }
+        (!self.skipping).then_some(line)
+    }
+}
 
-        tar.finish().context("Failed to finalize tar archive")?;
-        Ok(())
+/// mysqldump's own `INSERT INTO \`table\` VALUES ...` rows, as opposed to an
+/// INSERT inside a trigger or routine body, which may qualify its table
+fn is_row_data(line: &[u8]) -> bool {
+    if !line.starts_with(b"INSERT INTO `") {
+        return false;
     }
+    let text = String::from_utf8_lossy(line);
+    let table = text.split(" VALUES ").next().unwrap_or_default();
+    !table.contains("`.")
+}
 
-    pub fn validate_connection(&self) -> Result<()> {
-        let conn = &self.config.connection;
+/// Rewrites an `--all-databases` dump so only one database is replayed, under a new name
+struct DatabaseRename {
+    from: String,
+    to: String,
+    scope: RenameScope,
+}
 
-        // Basic validation
-        if conn.host.is_empty() {
-            anyhow::bail!("Database host cannot be empty");
+#[derive(Debug, PartialEq)]
+enum RenameScope {
+    /// Session setup before the first `USE` statement
+    Preamble,
+    /// Inside the database being restored
+    Selected,
+    /// Inside any other database in the dump
+    Skipped,
+}
+
+impl DatabaseRename {
+    fn new(from: &str, to: &str) -> Self {
+        Self {
+            from: from.to_string(),
+            to: to.to_string(),
+            scope: RenameScope::Preamble,
         }
+    }
 
-        if conn.username.is_empty() {
-            anyhow::bail!("Database username cannot be empty");
+    fn filter(&mut self, line: Vec<u8>) -> Option<Vec<u8>> {
+        let from_quoted = format!("`{}`", self.from);
+
+        if line.starts_with(b"CREATE DATABASE ") {
+            let text = String::from_utf8_lossy(&line);
+            return if text.contains(&from_quoted) {
+                Some(text.replacen(&from_quoted, &format!("`{}`", self.to), 1).into_bytes())
+            } else {
+                None
+            };
         }
 
-        Ok(())
+        if line.starts_with(b"USE `") {
+            let text = String::from_utf8_lossy(&line);
+            return if text.trim_end().trim_end_matches(';') == format!("USE {}", from_quoted) {
+                self.scope = RenameScope::Selected;
+                Some(format!("USE `{}`;\n", self.to).into_bytes())
+            } else {
+                self.scope = RenameScope::Skipped;
+                None
+            };
+        }
+
+        match self.scope {
+            RenameScope::Preamble => Some(line),
+            // Row data is replayed verbatim; only definitions can name the database
+            RenameScope::Selected if is_row_data(&line) => Some(line),
+            RenameScope::Selected => Some(self.rewrite_qualifiers(line)),
+            RenameScope::Skipped => None,
+        }
+    }
+
+    /// Point `from`.object references in views, triggers and routines at the new
+    /// database, in both the backquoted form mysqldump writes for views and the
+    /// bare form routine bodies may use
+    fn rewrite_qualifiers(&self, line: Vec<u8>) -> Vec<u8> {
+        let text = String::from_utf8_lossy(&line);
+        let quoted = format!("`{}`.", self.from.replace('`', "``"));
+        let bare = format!("{}.", self.from);
+        if !text.contains(&quoted) && !text.contains(&bare) {
+            return line;
+        }
+
+        let is_identifier = |c: char| c.is_alphanumeric() || matches!(c, '_' | '$' | '`' | '.');
+        let mut output = String::with_capacity(text.len());
+        let mut rest = text.as_ref();
+        while !rest.is_empty() {
+            let at_boundary = !output.chars().next_back().is_some_and(is_identifier);
+            if at_boundary && rest.starts_with(&quoted) {
+                output.push_str(&format!("`{}`.", self.to.replace('`', "``")));
+                rest = &rest[quoted.len()..];
+            } else if at_boundary && rest.starts_with(&bare) {
+                output.push_str(&format!("{}.", self.to));
+                rest = &rest[bare.len()..];
+            } else {
+                let c = rest.chars().next().unwrap_or_default();
+                output.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        output.into_bytes()
     }
 }
 
@@ -292,10 +431,10 @@ impl MysqlBackup {
 mod tests {
     use super::*;
 
-    fn create_test_config() -> (BackupConfig, StorageConfig) {
+    fn create_test_config() -> BackupConfig {
         use crate::config::{BackupMode, ConnectionConfig, StorageSelection, StorageReference};
         
-        let backup_config = BackupConfig {
+        BackupConfig {
             name: "test_mysql_backup".to_string(),
             driver: "mysql".to_string(),
             connection: ConnectionConfig {
@@ -315,36 +454,112 @@ mod tests {
             mode: BackupMode::Basic,
             parallel_jobs: 2,
             binary_path: None,
+            group: None,
+            timeout: None,
+            retry: None,
             retention: None,
-        };
-
-        let storage_config = StorageConfig {
-            driver: "local".to_string(),
-            path: Some(PathBuf::from("/tmp/backups")),
-            filename_prefix: Some("test_".to_string()),
-            bucket: None,
-            region: None,
-            prefix: None,
-            endpoint: None,
-            access_key_id: None,
-            secret_access_key: None,
-        };
-
-        (backup_config, storage_config)
+            verify: None,
+        }
     }
 
     #[test]
     fn test_validate_connection() {
-        let (config, storage_config) = create_test_config();
-        let backup = MysqlBackup::new(config, storage_config);
+        let config = create_test_config();
+        let backup = MysqlBackup::new(config);
         assert!(backup.validate_connection().is_ok());
     }
 
     #[test]
     fn test_validate_connection_empty_host() {
-        let (mut config, storage_config) = create_test_config();
+        let mut config = create_test_config();
         config.connection.host = "".to_string();
-        let backup = MysqlBackup::new(config, storage_config);
+        let backup = MysqlBackup::new(config);
         assert!(backup.validate_connection().is_err());
     }
+
+    #[test]
+    fn test_database_rename_filters_other_databases() {
+        let dump = [
+            "SET NAMES utf8mb4;\n",
+            "CREATE DATABASE /*!32312 IF NOT EXISTS*/ `other` /*!40100 DEFAULT CHARACTER SET utf8mb4 */;\n",
+            "USE `other`;\n",
+            "INSERT INTO t VALUES (1);\n",
+            "CREATE DATABASE /*!32312 IF NOT EXISTS*/ `testdb` /*!40100 DEFAULT CHARACTER SET utf8mb4 */;\n",
+            "USE `testdb`;\n",
+            "INSERT INTO t VALUES (2);\n",
+        ];
+
+        let mut rename = DatabaseRename::new("testdb", "testdb_restore");
+        let output: String = dump
+            .iter()
+            .filter_map(|line| rename.filter(line.as_bytes().to_vec()))
+            .map(|line| String::from_utf8(line).unwrap())
+            .collect();
+
+        assert_eq!(
+            output,
+            "SET NAMES utf8mb4;\n\
+             CREATE DATABASE /*!32312 IF NOT EXISTS*/ `testdb_restore` /*!40100 DEFAULT CHARACTER SET utf8mb4 */;\n\
+             USE `testdb_restore`;\n\
+             INSERT INTO t VALUES (2);\n"
+        );
+    }
+
+    #[test]
+    fn test_database_rename_rewrites_qualified_names() {
+        let dump = [
+            "USE `testdb`;\n",
+            "INSERT INTO `t` VALUES ('see `testdb`.t and testdb.t');\n",
+            "/*!50001 VIEW `v` AS select `testdb`.`t`.`id` AS `id` from `testdb`.`t` */;\n",
+            "CREATE TRIGGER audit AFTER INSERT ON t FOR EACH ROW\n",
+            "INSERT INTO `testdb`.`log` VALUES (mytestdb.x);\n",
+        ];
+
+        let mut rename = DatabaseRename::new("testdb", "testdb_restore");
+        let output: String = dump
+            .iter()
+            .filter_map(|line| rename.filter(line.as_bytes().to_vec()))
+            .map(|line| String::from_utf8(line).unwrap())
+            .collect();
+
+        assert_eq!(
+            output,
+            "USE `testdb_restore`;\n\
+             INSERT INTO `t` VALUES ('see `testdb`.t and testdb.t');\n\
+             /*!50001 VIEW `v` AS select `testdb_restore`.`t`.`id` AS `id` from `testdb_restore`.`t` */;\n\
+             CREATE TRIGGER audit AFTER INSERT ON t FOR EACH ROW\n\
+             INSERT INTO `testdb_restore`.`log` VALUES (mytestdb.x);\n"
+        );
+    }
+
+    #[test]
+    fn test_skip_system_databases() {
+        let dump = [
+            "SET NAMES utf8mb4;\n",
+            "CREATE DATABASE /*!32312 IF NOT EXISTS*/ `mysql` /*!40100 DEFAULT CHARACTER SET utf8mb4 */;\n",
+            "USE `mysql`;\n",
+            "INSERT INTO `user` VALUES ('root');\n",
+            "CREATE DATABASE /*!32312 IF NOT EXISTS*/ `app` /*!40100 DEFAULT CHARACTER SET utf8mb4 */;\n",
+            "USE `app`;\n",
+            "INSERT INTO t VALUES (1);\n",
+            "USE `sys`;\n",
+            "CREATE VIEW v AS SELECT 1;\n",
+        ];
+
+        let mut skip = SkipSystemDatabases::default();
+        let output: String = dump
+            .iter()
+            .filter_map(|line| skip.filter(line.as_bytes().to_vec()))
+            .map(|line| String::from_utf8(line).unwrap())
+            .collect();
+
+        assert_eq!(
+            output,
+            "SET NAMES utf8mb4;\n\
+             CREATE DATABASE /*!32312 IF NOT EXISTS*/ `app` /*!40100 DEFAULT CHARACTER SET utf8mb4 */;\n\
+             USE `app`;\n\
+             INSERT INTO t VALUES (1);\n"
+        );
+        assert_eq!(quoted_name(b"USE `we``ird`;").as_deref(), Some("we`ird"));
+    }
 }
@@ -1,96 +1,39 @@
-use crate::config::{BackupConfig, StorageConfig};
-use crate::fs_utils::move_file_with_fallback;
-use crate::storage;
+use crate::config::{BackupConfig, BinarySettings, ConnectionConfig};
+use crate::driver::{BackupDriver, DriverInfo};
+use crate::fs_utils::sibling_binary;
+use crate::restore::{self, ArchiveFormat, RestoreOptions, RestoreWorkspace};
 use anyhow::{Context, Result};
-use chrono::Local;
-use flate2::write::GzEncoder;
-use flate2::Compression;
-use std::fs::File;
-use std::io::Write;
-use std::path::PathBuf;
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use tokio::process::Command;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
+
+pub const DRIVER: DriverInfo = DriverInfo {
+    name: "postgresql",
+    display_name: "PostgreSQL",
+    dump_tool: "pg_dump",
+    extension: ".dump.gz",
+    create: |config| Box::new(PostgresBackup::new(config)),
+};
 
 pub struct PostgresBackup {
     config: BackupConfig,
-    storage_config: StorageConfig,
 }
 
 impl PostgresBackup {
-    pub fn new(config: BackupConfig, storage_config: StorageConfig) -> Self {
-        Self { config, storage_config }
-    }
-
-    pub async fn execute(&self) -> Result<String> {
-        info!("Starting PostgreSQL backup for: {}", self.config.name);
-
-        let timestamp = Local::now().format("%Y%m%d_%H%M%S");
-        
-        // Create storage backend
-        let storage_backend = storage::create_storage(&self.storage_config).await?;
-        
-        // For local storage, ensure the output directory exists
-        if self.storage_config.driver.to_lowercase() == "local" {
-            let path = self.storage_config.path.as_ref()
-                .context("Local storage requires 'path' configuration")?;
-            std::fs::create_dir_all(path)
-                .context("Failed to create output directory")?;
-        }
-
-        // Create a temporary directory for the backup file
-        let temp_dir = std::env::temp_dir().join(format!("dbackup_{}", uuid::Uuid::new_v4()));
-        std::fs::create_dir_all(&temp_dir)
-            .context("Failed to create temporary directory")?;
-
-        // Execute backup based on mode
-        let (temp_file_path, filename) = match self.config.mode {
-            crate::config::BackupMode::Basic => {
-                info!("Using basic mode (custom format with compression)...");
-                self.dump_basic(&temp_dir, timestamp).await?
-            }
-            crate::config::BackupMode::Parallel => {
-                info!(
-                    "Using parallel mode (directory format with {} jobs)...",
-                    self.config.parallel_jobs
-                );
-                self.dump_parallel(&temp_dir, timestamp).await?
-            }
-        };
-
-        // Move to final location or upload to S3
-        let final_location = if self.storage_config.driver.to_lowercase() == "local" {
-            // For local storage, file is already in the correct location
-            temp_file_path.display().to_string()
-        } else {
-            // For remote storage (S3), upload the file
-            storage_backend.store(&temp_file_path, &filename).await?
-        };
-
-        // Cleanup temporary directory
-        let _ = std::fs::remove_dir_all(&temp_dir);
-
-        info!("Backup completed successfully: {}", final_location);
-        Ok(final_location)
+    pub fn new(config: BackupConfig) -> Self {
+        Self { config }
     }
 
-    async fn dump_basic(&self, temp_dir: &PathBuf, timestamp: impl std::fmt::Display) -> Result<(PathBuf, String)> {
+    /// pg_dump invocation shared by both backup modes
+    fn pg_dump(&self) -> Command {
         let conn = &self.config.connection;
-        let filename = format!(
-            "{}{}.dump.gz",
-            self.storage_config.filename_prefix.as_ref().unwrap_or(&"backup_".to_string()),
-            timestamp
-        );
-        let output_path = temp_dir.join(&filename);
-
-        info!("Backing up to: {}", output_path.display());
 
         // Determine pg_dump path
-        let pg_dump_path = self.config.binary_path.as_ref()
-            .map(|p| p.as_path())
-            .unwrap_or_else(|| std::path::Path::new("pg_dump"));
+        let pg_dump_path = self.config.binary_path.as_deref()
+            .unwrap_or_else(|| Path::new("pg_dump"));
 
-        // Build pg_dump command with custom format and compression
         let mut cmd = Command::new(pg_dump_path);
 
         // Set environment variable for password
@@ -101,207 +44,257 @@ impl PostgresBackup {
         cmd.arg("--port").arg(conn.port.to_string());
         cmd.arg("--username").arg(&conn.username);
         cmd.arg("--dbname").arg(&conn.database);
+        cmd
+    }
 
-        // Custom format with compression level 9
-        cmd.arg("-Fc");
-        cmd.arg("--compress=9");
-        cmd.arg("--no-owner");
-        cmd.arg("--verbose");
+    /// A client tool: the configured path, else the one next to the job's pg_dump,
+    /// so both come from the same PostgreSQL release, else a PATH lookup
+    fn client_binary(&self, configured: Option<&Path>, name: &str) -> PathBuf {
+        configured
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| sibling_binary(self.config.binary_path.as_deref(), name))
+    }
 
-        // Capture stdout and stderr
-        cmd.stdout(Stdio::piped());
-        cmd.stderr(Stdio::piped());
+    /// Create the target database unless it already exists
+    pub async fn create_database(&self, options: &RestoreOptions) -> Result<()> {
+        let target = &options.target;
+        let client = self.client_binary(options.sql_client_path.as_deref(), "psql");
+
+        let exists = run_psql(
+            &client,
+            target,
+            MAINTENANCE_DATABASE,
+            &format!("SELECT 1 FROM pg_database WHERE datname = {}", quote_literal(&target.database)),
+        )
+        .await?;
+
+        if exists.trim() == "1" {
+            info!("Target database '{}' already exists", target.database);
+            return Ok(());
+        }
 
-        info!("Executing pg_dump with custom format and compression...");
-        let mut child = cmd.spawn().context("Failed to spawn pg_dump process")?;
+        info!("Creating target database '{}'", target.database);
+        run_psql(
+            &client,
+            target,
+            MAINTENANCE_DATABASE,
+            &format!("CREATE DATABASE {}", quote_identifier(&target.database)),
+        )
+        .await?;
+        Ok(())
+    }
+}
 
-        // Get stdout
-        let stdout = child
-            .stdout
-            .take()
-            .context("Failed to capture pg_dump stdout")?;
+#[async_trait]
+impl BackupDriver for PostgresBackup {
+    fn info(&self) -> &'static DriverInfo {
+        &DRIVER
+    }
 
-        // Create gzip compressed output file
-        let output_file = File::create(&output_path)
-            .context("Failed to create output file")?;
-        let mut encoder = GzEncoder::new(output_file, Compression::best());
+    fn validate_connection(&self) -> Result<()> {
+        let conn = &self.config.connection;
 
-        // Read from pg_dump and write compressed data
-        let mut reader = tokio::io::BufReader::new(stdout);
-        let mut buffer = Vec::new();
-        
-        tokio::io::copy(&mut reader, &mut buffer)
-            .await
-            .context("Failed to read pg_dump output")?;
-
-        encoder
-            .write_all(&buffer)
-            .context("Failed to write compressed data")?;
-        encoder.finish().context("Failed to finalize compression")?;
-
-        // Wait for the process to complete
-        let status = child.wait().await.context("pg_dump process failed")?;
-
-        if !status.success() {
-            let stderr = child.stderr.take();
-            if let Some(mut stderr) = stderr {
-                let mut error_output = String::new();
-                use tokio::io::AsyncReadExt;
-                stderr.read_to_string(&mut error_output).await?;
-                warn!("pg_dump stderr: {}", error_output);
-            }
-            anyhow::bail!("pg_dump failed with status: {}", status);
+        // Basic validation
+        if conn.host.is_empty() {
+            anyhow::bail!("Database host cannot be empty");
+        }
+
+        if conn.database.is_empty() {
+            anyhow::bail!("Database name cannot be empty");
         }
 
-        if self.storage_config.driver.to_lowercase() == "local" {
-            // For local storage, move file to the final location
-            let final_path = self.storage_config.path.as_ref()
-                .context("Local storage requires 'path' configuration")?
-                .join(&filename);
-            move_file_with_fallback(&output_path, &final_path)
-                .context("Failed to move backup file to final location")?;
-            info!("Backup compressed to {}", final_path.display());
-            Ok((final_path, filename))
-        } else {
-            // For remote storage, keep in temp directory for upload
-            info!("Backup compressed to {}", output_path.display());
-            Ok((output_path, filename))
+        if conn.username.is_empty() {
+            anyhow::bail!("Database username cannot be empty");
         }
+
+        Ok(())
     }
 
-    async fn dump_parallel(&self, temp_dir: &PathBuf, timestamp: impl std::fmt::Display) -> Result<(PathBuf, String)> {
-        let conn = &self.config.connection;
-        let basename = format!("{}{}", self.storage_config.filename_prefix.as_ref().unwrap_or(&"backup_".to_string()), timestamp);
-        
-        // Create temporary directory for directory format backup
-        let backup_tmp_dir = temp_dir.join(&basename);
-        std::fs::create_dir_all(&backup_tmp_dir)
-            .context("Failed to create temporary directory")?;
+    /// Restore a `.dump.gz` or `.dir.tar.gz` archive with pg_restore
+    async fn restore(&self, archive_path: &Path, options: &RestoreOptions) -> Result<()> {
+        let target = &options.target;
+        let format = ArchiveFormat::detect(archive_path)?;
 
-        info!("Using temporary directory: {}", backup_tmp_dir.display());
+        if options.create_database {
+            self.create_database(options).await?;
+        }
 
-        // Determine pg_dump path
-        let pg_dump_path = self.config.binary_path.as_ref()
-            .map(|p| p.as_path())
-            .unwrap_or_else(|| std::path::Path::new("pg_dump"));
+        info!(
+            "Restoring {} into PostgreSQL database '{}' on {}:{}",
+            archive_path.display(),
+            target.database,
+            target.host,
+            target.port
+        );
 
-        // Build pg_dump command with directory format and parallel jobs
-        let mut cmd = Command::new(pg_dump_path);
+        let pg_restore_path = self.client_binary(options.binary_path.as_deref(), "pg_restore");
+        let mut cmd = Command::new(pg_restore_path);
 
         // Set environment variable for password
-        cmd.env("PGPASSWORD", &conn.password);
+        cmd.env("PGPASSWORD", &target.password);
 
         // Add connection parameters
-        cmd.arg("--host").arg(&conn.host);
-        cmd.arg("--port").arg(conn.port.to_string());
-        cmd.arg("--username").arg(&conn.username);
-        cmd.arg("--dbname").arg(&conn.database);
-
-        // Directory format with parallel jobs
-        cmd.arg("-Fd"); // Directory format
-        cmd.arg("-j").arg(self.config.parallel_jobs.to_string()); // Parallel jobs
-        cmd.arg("-f").arg(&backup_tmp_dir); // Output directory
+        cmd.arg("--host").arg(&target.host);
+        cmd.arg("--port").arg(target.port.to_string());
+        cmd.arg("--username").arg(&target.username);
+        cmd.arg("--dbname").arg(&target.database);
         cmd.arg("--no-owner");
-        cmd.arg("--verbose");
 
-        // Capture stderr for logging
+        cmd.stdout(Stdio::null());
         cmd.stderr(Stdio::piped());
+        cmd.kill_on_drop(true);
 
-        info!(
-            "Executing pg_dump with directory format and {} parallel jobs...",
-            self.config.parallel_jobs
-        );
-        
-        let mut child = cmd.spawn().context("Failed to spawn pg_dump process")?;
-
-        // Wait for the process to complete
-        let status = child.wait().await.context("pg_dump process failed")?;
-
-        if !status.success() {
-            let stderr = child.stderr.take();
-            if let Some(mut stderr) = stderr {
-                let mut error_output = String::new();
-                use tokio::io::AsyncReadExt;
-                stderr.read_to_string(&mut error_output).await?;
-                warn!("pg_dump stderr: {}", error_output);
+        match format {
+            ArchiveFormat::Compressed => {
+                // The custom-format dump is gzipped once more; stream it through pg_restore's stdin
+                cmd.arg("--format=custom");
+                cmd.stdin(Stdio::piped());
+
+                info!("Executing pg_restore from decompressed stream...");
+                let mut child = cmd.spawn().context("Failed to spawn pg_restore process")?;
+                let stderr = restore::collect_output(child.stderr.take());
+                let mut stdin = child.stdin.take().context("Failed to capture pg_restore stdin")?;
+
+                let streamed = restore::stream_gzip_into(archive_path, &mut stdin).await;
+                drop(stdin);
+
+                restore::wait_for_client("pg_restore", child, stderr).await?;
+                streamed?;
+            }
+            ArchiveFormat::Directory => {
+                let workspace = RestoreWorkspace::new()?;
+                let dump_dir = workspace.path().join("dump");
+                restore::extract_tarball(archive_path, &dump_dir)?;
+
+                cmd.arg("--format=directory");
+                cmd.arg("-j").arg(options.jobs.max(1).to_string());
+                cmd.arg(&dump_dir);
+
+                info!("Executing pg_restore with {} parallel jobs...", options.jobs.max(1));
+                let mut child = cmd.spawn().context("Failed to spawn pg_restore process")?;
+                let stderr = restore::collect_output(child.stderr.take());
+
+                restore::wait_for_client("pg_restore", child, stderr).await?;
             }
-            
-            // Cleanup temp directory on failure
-            let _ = std::fs::remove_dir_all(&backup_tmp_dir);
-            anyhow::bail!("pg_dump failed with status: {}", status);
         }
 
-        // Compress the directory into a tar.gz file
-        let tar_filename = format!("{}.dir.tar.gz", basename);
-        let tar_path = temp_dir.join(&tar_filename);
-
-        info!("Compressing directory backup to {}", tar_path.display());
-        self.compress_directory(&backup_tmp_dir, &tar_path).await?;
-
-        // Cleanup the backup directory
-        std::fs::remove_dir_all(&backup_tmp_dir)
-            .context("Failed to remove temporary directory")?;
-
-        if self.storage_config.driver.to_lowercase() == "local" {
-            // For local storage, move file to the final location
-            let final_path = self.storage_config.path.as_ref()
-                .context("Local storage requires 'path' configuration")?
-                .join(&tar_filename);
-            move_file_with_fallback(&tar_path, &final_path)
-                .context("Failed to move backup file to final location")?;
-            info!("Backup compressed to {}", final_path.display());
-            Ok((final_path, tar_filename))
-        } else {
-            // For remote storage, keep in temp directory for upload
-            info!("Backup compressed to {}", tar_path.display());
-            Ok((tar_path, tar_filename))
+        info!("✓ Restore into '{}' completed", target.database);
+        Ok(())
+    }
+
+    /// Best-effort lookup of the source server version for the manifest
+    async fn server_version(&self, sql_client: Option<&Path>) -> Option<String> {
+        let psql_path = self.client_binary(sql_client, "psql");
+        let conn = &self.config.connection;
+        match run_psql(&psql_path, conn, &conn.database, "SHOW server_version").await {
+            Ok(version) => Some(version.trim().to_string()).filter(|v| !v.is_empty()),
+            Err(e) => {
+                debug!("Could not determine PostgreSQL server version: {}", e);
+                None
+            }
         }
     }
 
-    async fn compress_directory(&self, source_dir: &PathBuf, output_path: &PathBuf) -> Result<()> {
-        use tar::Builder;
+    /// Drop the target database if it exists
+    async fn drop_database(&self, options: &RestoreOptions) -> Result<()> {
+        let target = &options.target;
+        info!("Dropping database '{}'", target.database);
+        run_psql(
+            &self.client_binary(options.sql_client_path.as_deref(), "psql"),
+            target,
+            MAINTENANCE_DATABASE,
+            &format!("DROP DATABASE IF EXISTS {}", quote_identifier(&target.database)),
+        )
+        .await?;
+        Ok(())
+    }
 
-        let tar_gz = File::create(output_path)
-            .context("Failed to create tar.gz file")?;
-        let encoder = GzEncoder::new(tar_gz, Compression::default());
-        let mut tar = Builder::new(encoder);
+    /// Run a query against the target database and return its output
+    async fn run_query(&self, options: &RestoreOptions, sql: &str) -> Result<String> {
+        let psql_path = self.client_binary(options.sql_client_path.as_deref(), "psql");
+        run_psql(&psql_path, &options.target, &options.target.database, sql).await
+    }
 
-        // Add all files from the directory to the tar archive
-        tar.append_dir_all(".", source_dir)
-            .context("Failed to add directory to tar archive")?;
+    fn dump_stream_command(&self) -> Command {
+        let mut cmd = self.pg_dump();
 
-        tar.finish().context("Failed to finalize tar archive")?;
-        Ok(())
+        // Custom format with compression level 9
+        cmd.arg("-Fc");
+        cmd.arg("--compress=9");
+        cmd.arg("--no-owner");
+        cmd.arg("--verbose");
+        cmd
     }
 
-    pub fn validate_connection(&self) -> Result<()> {
-        let conn = &self.config.connection;
+    fn dump_directory_command(&self, dir: &Path) -> Command {
+        let mut cmd = self.pg_dump();
 
-        // Basic validation
-        if conn.host.is_empty() {
-            anyhow::bail!("Database host cannot be empty");
-        }
+        // Directory format with parallel jobs
+        cmd.arg("-Fd"); // Directory format
+        cmd.arg("-j").arg(self.config.parallel_jobs.to_string()); // Parallel jobs
+        cmd.arg("-f").arg(dir); // Output directory
+        cmd.arg("--no-owner");
+        cmd.arg("--verbose");
+        cmd
+    }
 
-        if conn.database.is_empty() {
-            anyhow::bail!("Database name cannot be empty");
-        }
+    fn restore_binary(&self, binary: &BinarySettings) -> Option<PathBuf> {
+        binary.pg_restore.clone()
+    }
 
-        if conn.username.is_empty() {
-            anyhow::bail!("Database username cannot be empty");
-        }
+    fn sql_client_binary(&self, binary: &BinarySettings) -> Option<PathBuf> {
+        binary.psql.clone()
+    }
+}
 
-        Ok(())
+/// Database psql connects to for server-level statements
+const MAINTENANCE_DATABASE: &str = "postgres";
+
+/// Run a single SQL statement with psql and return its unaligned, tuples-only output
+async fn run_psql(
+    psql_path: &Path,
+    connection: &ConnectionConfig,
+    database: &str,
+    sql: &str,
+) -> Result<String> {
+    let mut cmd = Command::new(psql_path);
+    cmd.env("PGPASSWORD", &connection.password);
+    cmd.arg("--host").arg(&connection.host);
+    cmd.arg("--port").arg(connection.port.to_string());
+    cmd.arg("--username").arg(&connection.username);
+    cmd.arg("--dbname").arg(database);
+    cmd.arg("--no-psqlrc");
+    cmd.arg("--tuples-only");
+    cmd.arg("--no-align");
+    cmd.arg("--set").arg("ON_ERROR_STOP=1");
+    cmd.arg("--command").arg(sql);
+
+    let output = cmd.output().await.context("Failed to run psql")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        warn!("psql stderr: {}", stderr);
+        anyhow::bail!("psql failed with status {}: {}", output.status, stderr.trim());
     }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    fn create_test_config() -> (BackupConfig, StorageConfig) {
+    fn create_test_config() -> BackupConfig {
         use crate::config::{BackupMode, ConnectionConfig, StorageSelection, StorageReference};
         
-        let backup_config = BackupConfig {
+        BackupConfig {
             name: "test_backup".to_string(),
             driver: "postgresql".to_string(),
             connection: ConnectionConfig {
@@ -321,36 +314,33 @@ mod tests {
             mode: BackupMode::Basic,
             parallel_jobs: 2,
             binary_path: None,
+            group: None,
+            timeout: None,
+            retry: None,
             retention: None,
-        };
-
-        let storage_config = StorageConfig {
-            driver: "local".to_string(),
-            path: Some(PathBuf::from("/tmp/backups")),
-            filename_prefix: Some("test_".to_string()),
-            bucket: None,
-            region: None,
-            prefix: None,
-            endpoint: None,
-            access_key_id: None,
-            secret_access_key: None,
-        };
-
-        (backup_config, storage_config)
+            verify: None,
+        }
     }
 
     #[test]
     fn test_validate_connection() {
-        let (config, storage_config) = create_test_config();
-        let backup = PostgresBackup::new(config, storage_config);
+        let config = create_test_config();
+        let backup = PostgresBackup::new(config);
         assert!(backup.validate_connection().is_ok());
     }
 
     #[test]
     fn test_validate_connection_empty_host() {
-        let (mut config, storage_config) = create_test_config();
+        let mut config = create_test_config();
         config.connection.host = "".to_string();
-        let backup = PostgresBackup::new(config, storage_config);
+        let backup = PostgresBackup::new(config);
         assert!(backup.validate_connection().is_err());
     }
+
+    #[test]
+    fn test_quoting() {
+        assert_eq!(quote_identifier("prod_restore"), "\"prod_restore\"");
+        assert_eq!(quote_identifier("we\"ird"), "\"we\"\"ird\"");
+        assert_eq!(quote_literal("o'brien"), "'o''brien'");
+    }
 }
@@ -0,0 +1,436 @@
+use crate::artifact;
+use crate::config::{BackupConfig, ConnectionConfig, StorageConfig};
+use crate::storage::{create_storage, StorageBackend};
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::process::Child;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Options shared by every driver's restore implementation
+#[derive(Debug, Clone)]
+pub struct RestoreOptions {
+    /// Connection the archive is restored into
+    pub target: ConnectionConfig,
+    /// Parallel jobs used for directory archives
+    pub jobs: u8,
+    /// Restore client binary (e.g. pg_restore); falls back to PATH lookup
+    pub binary_path: Option<PathBuf>,
+    /// SQL client (psql / mysql) used for administrative statements
+    pub sql_client_path: Option<PathBuf>,
+    /// Create the target database first if it does not exist
+    pub create_database: bool,
+    /// Replay the server's own schemas (users, grants) from a full-instance dump
+    pub include_system_databases: bool,
+}
+
+/// Whether restoring into `b` would overwrite the source `a`: the same database on
+/// the same server, or any database there if `a` is a whole-instance dump (no database)
+pub fn is_same_database(a: &ConnectionConfig, b: &ConnectionConfig) -> bool {
+    fn normalize_host(host: &str) -> String {
+        match host.trim().to_lowercase().as_str() {
+            "127.0.0.1" | "::1" | "[::1]" => "localhost".to_string(),
+            other => other.to_string(),
+        }
+    }
+
+    normalize_host(&a.host) == normalize_host(&b.host)
+        && a.port == b.port
+        && (a.database.is_empty() || a.database == b.database)
+}
+
+/// Archive layouts produced by the backup drivers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    /// Single gzipped dump stream (`.dump.gz`, `.sql.gz`)
+    Compressed,
+    /// Gzipped tarball of a dump directory (`.dir.tar.gz`)
+    Directory,
+}
+
+impl ArchiveFormat {
+    pub fn detect(path: &Path) -> Result<Self> {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .context("Archive path has no file name")?;
+
+        if name.ends_with(".dir.tar.gz") {
+            Ok(ArchiveFormat::Directory)
+        } else if name.ends_with(".dump.gz") || name.ends_with(".sql.gz") {
+            Ok(ArchiveFormat::Compressed)
+        } else {
+            anyhow::bail!(
+                "Unrecognized archive format: '{}'. Expected .dump.gz, .sql.gz or .dir.tar.gz",
+                name
+            )
+        }
+    }
+}
+
+/// Temporary working directory for a restore, removed on drop
+pub struct RestoreWorkspace {
+    path: PathBuf,
+}
+
+impl RestoreWorkspace {
+    pub fn new() -> Result<Self> {
+        let path = std::env::temp_dir().join(format!("dbackup_restore_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&path)
+            .context("Failed to create temporary restore directory")?;
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for RestoreWorkspace {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Locate an archive on disk, downloading it from the backup's storage if needed.
+///
+/// `archive` may be a local file path, a file name inside a local storage
+/// directory, or a key (or `s3://` URL) inside an S3 storage.
+pub async fn fetch_archive(
+    archive: &str,
+    storage_config: &StorageConfig,
+    workspace: &RestoreWorkspace,
+) -> Result<PathBuf> {
+    let direct = PathBuf::from(archive);
+    if direct.is_file() {
+        return Ok(direct);
+    }
+
+    let storage = create_storage(storage_config).await?;
+    let key = storage.resolve_key(archive);
+
+    // Local archives are restored in place
+    if let Some(path) = storage.local_path(&key) {
+        if !path.is_file() {
+            anyhow::bail!("Archive not found: {}", path.display());
+        }
+        return Ok(path);
+    }
+
+    if !storage.exists(&key).await? {
+        anyhow::bail!("Archive not found: {}", storage.location_of(&key));
+    }
+
+    let filename = key.rsplit('/').next().unwrap_or(&key).to_string();
+    let destination = workspace.path().join(filename);
+    download(storage.as_ref(), &key, &destination).await?;
+    Ok(destination)
+}
+
+/// Copy an object from storage to a local file
+async fn download(storage: &dyn StorageBackend, key: &str, destination: &Path) -> Result<()> {
+    info!("Downloading {} to {}", storage.location_of(key), destination.display());
+
+    let mut reader = storage.get(key).await?;
+    let mut file = tokio::fs::File::create(destination)
+        .await
+        .context(format!("Failed to create {}", destination.display()))?;
+    let bytes = tokio::io::copy(&mut reader, &mut file)
+        .await
+        .context(format!("Failed to download {}", storage.location_of(key)))?;
+    file.flush().await.context("Failed to flush downloaded archive")?;
+
+    debug!("Downloaded {} bytes", bytes);
+    Ok(())
+}
+
+/// Find the newest archive of `backup` in its storage, returned as a reference `fetch_archive` accepts.
+///
+/// Other jobs' archives in a shared location are never picked.
+pub async fn find_latest_archive(backup: &BackupConfig, storage_config: &StorageConfig) -> Result<String> {
+    let storage = create_storage(storage_config).await?;
+    artifact::job_artifacts(storage.as_ref(), backup, artifact::filename_prefix(storage_config))
+        .await?
+        .into_iter()
+        .max_by_key(|a| a.created)
+        .map(|a| a.object.key)
+        .context(format!("No archives of backup '{}' found in storage", backup.name))
+}
+
+/// Unpack a `.dir.tar.gz` archive into `destination`
+pub fn extract_tarball(archive: &Path, destination: &Path) -> Result<()> {
+    info!("Extracting {} to {}", archive.display(), destination.display());
+    let file = File::open(archive)
+        .context(format!("Failed to open archive: {}", archive.display()))?;
+    let mut tar = tar::Archive::new(GzDecoder::new(file));
+    tar.unpack(destination)
+        .context("Failed to extract archive")?;
+    Ok(())
+}
+
+/// Report streaming progress every this many bytes
+const PROGRESS_INTERVAL: u64 = 256 * 1024 * 1024;
+
+/// Open a dump file for reading, transparently decompressing `.gz` files
+pub fn open_dump(path: &Path) -> Result<Box<dyn Read + Send>> {
+    let file = File::open(path)
+        .context(format!("Failed to open dump: {}", path.display()))?;
+    if path.extension().and_then(|e| e.to_str()) == Some("gz") {
+        Ok(Box::new(GzDecoder::new(file)))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// Stream the decompressed contents of a gzip file into `writer`.
+///
+/// Decompression runs on a blocking thread and is handed over in chunks, so
+/// arbitrarily large archives never have to fit in memory.
+pub async fn stream_gzip_into<W>(archive: &Path, writer: &mut W) -> Result<u64>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut reader = open_dump(archive)?;
+    let (tx, rx) = tokio::sync::mpsc::channel::<std::io::Result<Vec<u8>>>(8);
+
+    let reader_task = tokio::task::spawn_blocking(move || {
+        loop {
+            let mut buffer = vec![0u8; 64 * 1024];
+            match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => {
+                    buffer.truncate(n);
+                    if tx.blocking_send(Ok(buffer)).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    let _ = tx.blocking_send(Err(e));
+                    break;
+                }
+            }
+        }
+    });
+
+    forward_chunks(rx, reader_task, writer).await
+}
+
+/// Stream a text dump line by line into `writer`.
+///
+/// `filter` sees every line (including its trailing newline) and may rewrite
+/// it or return `None` to drop it.
+pub async fn stream_lines_into<W, F>(
+    reader: Box<dyn Read + Send>,
+    writer: &mut W,
+    mut filter: F,
+) -> Result<u64>
+where
+    W: AsyncWrite + Unpin,
+    F: FnMut(Vec<u8>) -> Option<Vec<u8>> + Send + 'static,
+{
+    let (tx, rx) = tokio::sync::mpsc::channel::<std::io::Result<Vec<u8>>>(8);
+
+    let reader_task = tokio::task::spawn_blocking(move || {
+        let mut reader = BufReader::new(reader);
+        let mut batch = Vec::with_capacity(64 * 1024);
+        loop {
+            let mut line = Vec::new();
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) => break,
+                Ok(_) => {
+                    if let Some(line) = filter(line) {
+                        batch.extend_from_slice(&line);
+                    }
+                    if batch.len() >= 64 * 1024
+                        && tx.blocking_send(Ok(std::mem::take(&mut batch))).is_err()
+                    {
+                        return;
+                    }
+                }
+                Err(e) => {
+                    let _ = tx.blocking_send(Err(e));
+                    return;
+                }
+            }
+        }
+        if !batch.is_empty() {
+            let _ = tx.blocking_send(Ok(batch));
+        }
+    });
+
+    forward_chunks(rx, reader_task, writer).await
+}
+
+/// Write chunks produced by a blocking reader task into `writer`, logging progress
+async fn forward_chunks<W>(
+    mut rx: tokio::sync::mpsc::Receiver<std::io::Result<Vec<u8>>>,
+    reader_task: JoinHandle<()>,
+    writer: &mut W,
+) -> Result<u64>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut total = 0u64;
+    let mut next_report = PROGRESS_INTERVAL;
+    while let Some(chunk) = rx.recv().await {
+        let chunk = chunk.context("Failed to read dump")?;
+        writer.write_all(&chunk).await.context("Failed to write restore data")?;
+        total += chunk.len() as u64;
+        if total >= next_report {
+            info!("Restored {} MiB so far...", total / (1024 * 1024));
+            next_report += PROGRESS_INTERVAL;
+        }
+    }
+    writer.flush().await.context("Failed to flush restore data")?;
+    reader_task.await.context("Dump reader task failed")?;
+
+    debug!("Streamed {} byte(s) of restore data", total);
+    Ok(total)
+}
+
+/// Drain a child's stderr in the background so a chatty client can't block on a full pipe
+pub fn collect_output<R>(stream: Option<R>) -> JoinHandle<String>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut output = String::new();
+        if let Some(mut stream) = stream {
+            let _ = stream.read_to_string(&mut output).await;
+        }
+        output
+    })
+}
+
+/// Wait for a database client to exit, surfacing its stderr on failure
+pub async fn wait_for_client(program: &str, mut child: Child, stderr: JoinHandle<String>) -> Result<()> {
+    let status = child.wait().await.context(format!("{} process failed", program))?;
+    let error_output = stderr.await.unwrap_or_default();
+
+    if !status.success() {
+        warn!("{} stderr: {}", program, error_output);
+        let tail: Vec<&str> = error_output.lines().rev().take(5).collect();
+        let tail: Vec<&str> = tail.into_iter().rev().collect();
+        anyhow::bail!("{} failed with status {}: {}", program, status, tail.join("\n"));
+    }
+
+    if !error_output.trim().is_empty() {
+        debug!("{} stderr: {}", program, error_output);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connection(host: &str, port: u16, database: &str) -> ConnectionConfig {
+        ConnectionConfig {
+            uri: None,
+            host: host.to_string(),
+            port,
+            username: "postgres".to_string(),
+            password: "password".to_string(),
+            database: database.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_is_same_database() {
+        let source = connection("localhost", 5432, "prod");
+        assert!(is_same_database(&source, &connection("127.0.0.1", 5432, "prod")));
+        assert!(is_same_database(&source, &connection("LOCALHOST", 5432, "prod")));
+        assert!(!is_same_database(&source, &connection("localhost", 5432, "prod_restore")));
+        assert!(!is_same_database(&source, &connection("localhost", 5433, "prod")));
+        assert!(!is_same_database(&source, &connection("staging.internal", 5432, "prod")));
+
+        // A whole-instance dump overwrites every database on its server
+        let instance = connection("localhost", 3306, "");
+        assert!(is_same_database(&instance, &connection("localhost", 3306, "scratch")));
+        assert!(!is_same_database(&instance, &connection("replica.internal", 3306, "scratch")));
+    }
+
+    #[test]
+    fn test_detect_archive_format() {
+        assert_eq!(
+            ArchiveFormat::detect(Path::new("/tmp/backup_20260101_020000.dump.gz")).unwrap(),
+            ArchiveFormat::Compressed
+        );
+        assert_eq!(
+            ArchiveFormat::detect(Path::new("mysql_20260101_020000.sql.gz")).unwrap(),
+            ArchiveFormat::Compressed
+        );
+        assert_eq!(
+            ArchiveFormat::detect(Path::new("backup_20260101_020000.dir.tar.gz")).unwrap(),
+            ArchiveFormat::Directory
+        );
+        assert!(ArchiveFormat::detect(Path::new("backup.tar")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stream_gzip_into() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let workspace = RestoreWorkspace::new().unwrap();
+        let archive = workspace.path().join("data.sql.gz");
+        let mut encoder = GzEncoder::new(File::create(&archive).unwrap(), Compression::default());
+        encoder.write_all(b"SELECT 1;\n").unwrap();
+        encoder.finish().unwrap();
+
+        let mut output = Vec::new();
+        let written = stream_gzip_into(&archive, &mut output).await.unwrap();
+        assert_eq!(written, 10);
+        assert_eq!(output, b"SELECT 1;\n");
+    }
+
+    #[tokio::test]
+    async fn test_stream_lines_into_filters_lines() {
+        let dump: Box<dyn Read + Send> = Box::new(std::io::Cursor::new(b"keep 1\ndrop\nkeep 2".to_vec()));
+        let mut output = Vec::new();
+        stream_lines_into(dump, &mut output, |line| {
+            if line.starts_with(b"drop") { None } else { Some(line) }
+        })
+        .await
+        .unwrap();
+        assert_eq!(output, b"keep 1\nkeep 2");
+    }
+
+    #[tokio::test]
+    async fn test_find_latest_archive_ignores_other_jobs() {
+        use crate::manifest::{ArchiveDigest, BackupManifest};
+
+        let root = std::env::temp_dir().join(format!("dbackup_restore_test_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let storage_config: StorageConfig =
+            serde_yaml::from_str(&format!("driver: local\npath: {}", root.display())).unwrap();
+        let backup = |name: &str| -> BackupConfig {
+            serde_yaml::from_str(&format!(
+                "name: {}\ndriver: postgresql\nconnection: {{host: db, port: 5432, username: u, password: p, database: d}}",
+                name
+            ))
+            .unwrap()
+        };
+        let digest = ArchiveDigest { sha256: String::new(), size: 0 };
+        let write = |name: &str| std::fs::write(root.join(name), b"").unwrap();
+
+        write("backup_20260101_020000.dump.gz");
+        // Newer archives of another job and of a MySQL job in the same directory
+        write("backup_20260102_020000.dump.gz");
+        BackupManifest::new(&backup("theirs"), "backup_20260102_020000.dump.gz", digest, chrono::Utc::now(), None)
+            .write_to_dir(&root)
+            .unwrap();
+        write("backup_20260103_020000.sql.gz");
+
+        let latest = find_latest_archive(&backup("ours"), &storage_config).await.unwrap();
+        assert_eq!(latest, "backup_20260101_020000.dump.gz");
+        assert!(find_latest_archive(&backup("other"), &storage_config).await.is_ok());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
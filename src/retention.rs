@@ -1,9 +1,15 @@
 use anyhow::{Context, Result};
 use std::time::Duration;
-use std::time::SystemTime;
-use std::path::Path;
-use crate::config::StorageConfig;
-use tracing::{info, debug};
+use chrono::{Local, NaiveDateTime};
+use crate::artifact::{filename_prefix, format_size, job_artifacts, JobArtifact};
+use crate::fs_utils::available_space;
+use crate::config::{BackupConfig, BackupMode, Config, RetentionConfig, RetentionPolicy, StorageConfig};
+use crate::history;
+use crate::storage::{create_storage, StorageBackend};
+use tracing::{debug, info, warn};
+
+/// Unless configured otherwise, retention never deletes the newest backup
+const DEFAULT_MIN_KEEP: usize = 1;
 
 /// Parses duration strings like "1d", "2d", "1w", "5m", "30s"
 /// Returns the duration in seconds
@@ -41,83 +47,754 @@ pub fn parse_duration(duration_str: &str) -> Result<Duration> {
     Ok(Duration::from_secs(seconds))
 }
 
-/// Apply retention policy to local storage
-pub fn apply_local_retention(path: &Path, retention_days: &str) -> Result<()> {
-    let retention_duration = parse_duration(retention_days)
-        .context(format!("Invalid retention policy: '{}'", retention_days))?;
-    
-    if !path.exists() {
-        debug!("Backup path does not exist: {}", path.display());
-        return Ok(());
+/// Parses sizes like "200GB", "1.5TiB", "512M" into bytes.
+/// K/M/G/T and KB/MB/GB/TB are decimal; KiB/MiB/GiB/TiB are binary.
+pub fn parse_size(size_str: &str) -> Result<u64> {
+    let size_str = size_str.trim();
+
+    if size_str.is_empty() {
+        anyhow::bail!("Size string cannot be empty");
     }
-    
-    let now = SystemTime::now();
-    let cutoff_time = now - retention_duration;
-    
-    info!("Applying retention policy: {} to local storage: {}", retention_days, path.display());
-    
+
+    let pos = size_str.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(size_str.len());
+    let (num_str, unit) = size_str.split_at(pos);
+
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1_000,
+        "m" | "mb" => 1_000_000,
+        "g" | "gb" => 1_000_000_000,
+        "t" | "tb" => 1_000_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        _ => anyhow::bail!(
+            "Unknown size unit '{}'. Supported: B, K, KB, M, MB, G, GB, T, TB (powers of 1000) \
+             and KiB, MiB, GiB, TiB (powers of 1024), e.g. '200GB'",
+            unit
+        ),
+    };
+
+    // Whole numbers stay exact; only fractions go through floating point
+    let bytes = if num_str.contains('.') {
+        let num: f64 = num_str.parse()
+            .context(format!("Invalid number in size: '{}'", num_str))?;
+        Some(num * multiplier as f64).filter(|bytes| *bytes < u64::MAX as f64).map(|bytes| bytes as u64)
+    } else {
+        let num: u64 = num_str.parse()
+            .context(format!("Invalid number in size: '{}'", num_str))?;
+        num.checked_mul(multiplier)
+    };
+    bytes.context(format!("Size is too large: '{}'", size_str))
+}
+
+/// Total size of everything in a storage location
+async fn storage_usage(storage: &dyn StorageBackend) -> Result<u64> {
+    Ok(storage.list("").await?.iter().map(|o| o.size).sum())
+}
+
+/// Retention rules with durations parsed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionRules {
+    /// Newest archives that are never deleted, whatever the other rules say
+    pub min_keep: usize,
+    pub max_age: Option<Duration>,
+    pub keep_last: usize,
+    pub keep_hourly: usize,
+    pub keep_daily: usize,
+    pub keep_weekly: usize,
+    pub keep_monthly: usize,
+    pub keep_yearly: usize,
+    /// Quota in bytes for everything under the storage path or prefix
+    pub max_total_size: Option<u64>,
+}
+
+impl RetentionRules {
+    pub fn from_config(config: &RetentionConfig) -> Result<Self> {
+        let policy = match config {
+            RetentionConfig::MaxAge(max_age) => RetentionPolicy {
+                max_age: Some(max_age.clone()),
+                ..Default::default()
+            },
+            RetentionConfig::Policy(policy) => policy.clone(),
+        };
+
+        let max_age = policy.max_age
+            .as_deref()
+            .map(|max_age| parse_duration(max_age).context(format!("Invalid retention max_age: '{}'", max_age)))
+            .transpose()?;
+
+        Ok(Self {
+            min_keep: policy.min_keep.unwrap_or(DEFAULT_MIN_KEEP),
+            max_age,
+            keep_last: policy.keep_last.unwrap_or(0),
+            keep_hourly: policy.keep_hourly.unwrap_or(0),
+            keep_daily: policy.keep_daily.unwrap_or(0),
+            keep_weekly: policy.keep_weekly.unwrap_or(0),
+            keep_monthly: policy.keep_monthly.unwrap_or(0),
+            keep_yearly: policy.keep_yearly.unwrap_or(0),
+            max_total_size: policy.max_total_size.as_deref().map(parse_quota).transpose()?,
+        })
+    }
+
+    /// Rules for a backup job: its retention policy plus the storage quota,
+    /// or `None` when neither is configured
+    pub fn for_backup(backup: &BackupConfig, storage_config: &StorageConfig) -> Result<Option<Self>> {
+        let mut rules = match &backup.retention {
+            Some(retention) => Self::from_config(retention)?,
+            None if storage_config.max_total_size.is_some() => Self {
+                min_keep: DEFAULT_MIN_KEEP,
+                ..Default::default()
+            },
+            None => return Ok(None),
+        };
+
+        if rules.max_total_size.is_none() {
+            rules.max_total_size = storage_config.max_total_size.as_deref().map(parse_quota).transpose()?;
+        }
+        Ok(Some(rules))
+    }
+
+    /// Whether no age or keep rule could select an archive for deletion
+    fn is_empty(&self) -> bool {
+        *self == Self {
+            min_keep: self.min_keep,
+            max_total_size: self.max_total_size,
+            ..Default::default()
+        }
+    }
+
+    /// Periodic rules as (name, count, bucket format)
+    fn buckets(&self) -> [(&'static str, usize, &'static str); 5] {
+        [
+            ("keep_hourly", self.keep_hourly, "%Y-%m-%d %H:00"),
+            ("keep_daily", self.keep_daily, "%Y-%m-%d"),
+            ("keep_weekly", self.keep_weekly, "%G-W%V"),
+            ("keep_monthly", self.keep_monthly, "%Y-%m"),
+            ("keep_yearly", self.keep_yearly, "%Y"),
+        ]
+    }
+}
+
+fn parse_quota(quota: &str) -> Result<u64> {
+    parse_size(quota).context(format!("Invalid max_total_size: '{}'", quota))
+}
+
+/// Indices of the newest `n` good archives, newest first
+fn newest_good(candidates: &[RetentionCandidate], n: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..candidates.len()).filter(|&i| candidates[i].good).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(candidates[i].created));
+    order.truncate(n);
+    order
+}
+
+/// What retention knows about one archive
+#[derive(Debug, Clone)]
+pub struct RetentionCandidate {
+    /// When the backup was taken, in local time (the drivers name archives after local time)
+    pub created: NaiveDateTime,
+    pub size: u64,
+    /// A completed backup that `min_keep` may protect; see [`is_good`]
+    pub good: bool,
+}
+
+/// Whether an archive is a completed backup: its manifest is present and records
+/// exactly the stored size, so a truncated upload or a dump that died before its
+/// manifest was written never counts. Jobs whose archives all predate manifests
+/// fall back to treating any non-empty archive as good.
+fn is_good(artifact: &JobArtifact, legacy: bool) -> bool {
+    match &artifact.manifest {
+        Some(manifest) => manifest.size == artifact.object.size && artifact.object.size > 0,
+        None => legacy && artifact.object.size > 0,
+    }
+}
+
+/// Whether an archive is kept, and why
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verdict {
+    pub keep: bool,
+    pub reason: String,
+}
+
+/// Decide which archives to keep. Verdicts are returned in the order of `candidates`.
+///
+/// Periodic rules keep the newest archive of each of the last N hours, days,
+/// ISO weeks, months or years that contain a backup.
+pub fn select(rules: &RetentionRules, candidates: &[RetentionCandidate], now: NaiveDateTime) -> Vec<Verdict> {
+    if rules.is_empty() {
+        return candidates.iter()
+            .map(|_| Verdict { keep: true, reason: "no age or keep rules".to_string() })
+            .collect();
+    }
+
+    // Newest first
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(candidates[i].created));
+
+    let mut reasons: Vec<Vec<String>> = vec![Vec::new(); candidates.len()];
+
+    for (rank, &i) in order.iter().take(rules.keep_last).enumerate() {
+        reasons[i].push(format!("keep_last #{}", rank + 1));
+    }
+
+    for (rule, count, format) in rules.buckets() {
+        let mut last_bucket: Option<String> = None;
+        let mut kept = 0;
+        for &i in &order {
+            if kept >= count {
+                break;
+            }
+            let bucket = candidates[i].created.format(format).to_string();
+            if last_bucket.as_deref() != Some(bucket.as_str()) {
+                reasons[i].push(format!("{} ({})", rule, bucket));
+                last_bucket = Some(bucket);
+                kept += 1;
+            }
+        }
+    }
+
+    let mut expired = vec![false; candidates.len()];
+    if let Some(max_age) = rules.max_age {
+        // A max_age reaching past the calendar range expires nothing
+        let cutoff = chrono::Duration::from_std(max_age).ok()
+            .and_then(|max_age| now.checked_sub_signed(max_age));
+        for (i, candidate) in candidates.iter().enumerate() {
+            match cutoff {
+                Some(cutoff) if candidate.created < cutoff => expired[i] = true,
+                _ => reasons[i].push("within max_age".to_string()),
+            }
+        }
+    }
+
+    // Safety floor: the newest good archives survive even if every other
+    // rule would drop them, e.g. after backups have been failing for a while
+    for (rank, i) in newest_good(candidates, rules.min_keep).into_iter().enumerate() {
+        if reasons[i].is_empty() {
+            reasons[i].push(format!("min_keep #{}", rank + 1));
+        }
+    }
+
+    reasons.into_iter()
+        .zip(expired)
+        .map(|(reasons, expired)| match (reasons.is_empty(), expired) {
+            (false, _) => Verdict { keep: true, reason: reasons.join(", ") },
+            (true, true) => Verdict { keep: false, reason: "older than max_age".to_string() },
+            (true, false) => Verdict { keep: false, reason: "not selected by any keep rule".to_string() },
+        })
+        .collect()
+}
+
+/// Mark the oldest archives still kept for deletion until `usage` (bytes under
+/// the storage root, these archives included) plus `reserve` fits the quota.
+/// The newest `min_keep` archives are never marked. Returns the projected usage
+/// once the plan has been carried out.
+pub fn apply_quota(
+    rules: &RetentionRules,
+    candidates: &[RetentionCandidate],
+    verdicts: &mut [Verdict],
+    usage: u64,
+    reserve: u64,
+) -> u64 {
+    let expired: u64 = candidates.iter()
+        .zip(verdicts.iter())
+        .filter(|(_, v)| !v.keep)
+        .map(|(c, _)| c.size)
+        .sum();
+    let mut projected = usage.saturating_sub(expired);
+
+    let Some(quota) = rules.max_total_size else { return projected };
+    let protected = newest_good(candidates, rules.min_keep);
+
+    // Oldest first
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    order.sort_by_key(|&i| candidates[i].created);
+
+    for i in order {
+        if projected.saturating_add(reserve) <= quota {
+            break;
+        }
+        if !verdicts[i].keep || protected.contains(&i) {
+            continue;
+        }
+        verdicts[i] = Verdict {
+            keep: false,
+            reason: format!("over max_total_size ({} of {} used)", format_size(projected), format_size(quota)),
+        };
+        projected = projected.saturating_sub(candidates[i].size);
+    }
+    projected
+}
+
+/// Retention decision for one of a job's archives
+#[derive(Debug, Clone)]
+pub struct RetentionDecision {
+    pub key: String,
+    pub manifest_key: Option<String>,
+    pub created: NaiveDateTime,
+    pub size: u64,
+    pub verdict: Verdict,
+}
+
+/// Evaluate retention rules against a backup job's archives, oldest first, without deleting anything.
+///
+/// `reserve` is room the quota must leave free, e.g. for the backup about to run.
+pub async fn plan_retention(
+    storage: &dyn StorageBackend,
+    backup: &BackupConfig,
+    filename_prefix: &str,
+    rules: &RetentionRules,
+    reserve: u64,
+) -> Result<Vec<RetentionDecision>> {
+    let mut artifacts = job_artifacts(storage, backup, filename_prefix).await?;
+    artifacts.sort_by_key(|a| a.created);
+
+    let legacy = artifacts.iter().all(|a| a.manifest.is_none());
+    let candidates: Vec<RetentionCandidate> = artifacts.iter()
+        .map(|a| RetentionCandidate { created: a.created, size: a.object.size, good: is_good(a, legacy) })
+        .collect();
+    let mut verdicts = select(rules, &candidates, Local::now().naive_local());
+    if rules.max_total_size.is_some() {
+        let usage = storage_usage(storage).await?;
+        apply_quota(rules, &candidates, &mut verdicts, usage, reserve);
+    }
+
+    Ok(artifacts.into_iter()
+        .zip(verdicts)
+        .map(|(a, verdict)| RetentionDecision {
+            key: a.object.key,
+            manifest_key: a.manifest_key,
+            created: a.created,
+            size: a.object.size,
+            verdict,
+        })
+        .collect())
+}
+
+/// Delete every archive (and its manifest) a plan does not keep, returning how many were removed
+pub async fn delete_expired(storage: &dyn StorageBackend, decisions: &[RetentionDecision]) -> usize {
     let mut deleted_count = 0;
-    
-    // List files in the directory
-    for entry in std::fs::read_dir(path)
-        .context("Failed to read backup directory")? {
-        
-        let entry = entry.context("Failed to read directory entry")?;
-        let file_path = entry.path();
-        
-        // Skip directories
-        if file_path.is_dir() {
+    for decision in decisions {
+        let key = &decision.key;
+        if decision.verdict.keep {
+            debug!("Keeping {}: {}", key, decision.verdict.reason);
             continue;
         }
-        
-        // Check file modification time
-        if let Ok(metadata) = std::fs::metadata(&file_path) {
-            if let Ok(modified) = metadata.modified() {
-                if modified < cutoff_time {
-                    match std::fs::remove_file(&file_path) {
-                        Ok(_) => {
-                            info!("Deleted old backup: {}", file_path.display());
-                            deleted_count += 1;
-                        }
-                        Err(e) => {
-                            debug!("Failed to delete backup {}: {}", file_path.display(), e);
-                        }
-                    }
-                }
+
+        match storage.delete(key).await {
+            Ok(_) => {
+                info!("Deleted old backup: {} ({})", storage.location_of(key), decision.verdict.reason);
+                deleted_count += 1;
+            }
+            Err(e) => {
+                warn!("Failed to delete backup {}: {}", storage.location_of(key), e);
+                continue;
+            }
+        }
+
+        if let Some(manifest_key) = &decision.manifest_key {
+            if let Err(e) = storage.delete(manifest_key).await {
+                warn!("Failed to delete manifest {}: {}", storage.location_of(manifest_key), e);
             }
         }
     }
+    deleted_count
+}
+
+/// Apply retention rules to a backup job's archives, deleting every archive
+/// (and its manifest) the rules do not keep
+pub async fn apply_retention(
+    storage: &dyn StorageBackend,
+    backup: &BackupConfig,
+    filename_prefix: &str,
+    rules: &RetentionRules,
+) -> Result<()> {
+    info!("Applying retention policy to '{}' in {}", backup.name, storage.get_location_display());
+
+    let decisions = plan_retention(storage, backup, filename_prefix, rules, 0).await?;
+    let deleted_count = delete_expired(storage, &decisions).await;
     
     info!("Retention cleanup removed {} backup(s)", deleted_count);
     Ok(())
 }
 
-/// Apply retention policy to S3 storage (async)
-pub async fn apply_s3_retention(
-    bucket: &str,
-    prefix: &str,
-    retention_policy: &str,
-    storage_config: &StorageConfig,
-) -> Result<()> {
-    use crate::storage::S3Storage;
-    
-    let retention_duration = parse_duration(retention_policy)
-        .context(format!("Invalid retention policy: '{}'", retention_policy))?;
-    
-    let s3_storage = S3Storage::new(storage_config).await?;
-    
-    info!("Applying retention policy: {} to S3: s3://{}/{}", 
-          retention_policy, bucket, prefix);
-    
-    s3_storage.cleanup_old_backups(&retention_duration).await?;
-    
+/// Apply a backup's retention policy to its storage after a successful run.
+///
+/// Failures are logged rather than returned: a backup that completed is not
+/// turned into a failed one because old archives could not be cleaned up.
+pub async fn enforce(backup: &BackupConfig, storage_config: &StorageConfig) {
+    let result = async {
+        let Some(rules) = RetentionRules::for_backup(backup, storage_config)? else { return Ok(()) };
+        let storage = create_storage(storage_config).await?;
+        apply_retention(storage.as_ref(), backup, filename_prefix(storage_config), &rules).await
+    }.await;
+
+    if let Err(e) = result {
+        warn!("Retention cleanup for '{}' failed: {:#}", backup.name, e);
+    }
+}
+
+/// Fail fast before a backup starts if it is not going to fit.
+///
+/// With a `max_total_size` quota, the next archive is estimated from the job's
+/// newest one in storage; when that would push the storage over its quota, the
+/// job's retention runs first to make room, and the backup is refused if it
+/// still does not fit. Without a quota the storage is not listed at all (a
+/// write-only credential is enough) and the estimate comes from the last
+/// successful run in the history. Either way the backup is refused when the
+/// temporary or local storage filesystem has less free space than it needs.
+pub async fn preflight(config: &Config, backup: &BackupConfig, storage_config: &StorageConfig) -> Result<()> {
+    let storage = create_storage(storage_config).await?;
+
+    let quota = RetentionRules::for_backup(backup, storage_config)?
+        .filter(|rules| rules.max_total_size.is_some());
+    let estimate = match quota {
+        Some(rules) => enforce_quota(storage.as_ref(), backup, filename_prefix(storage_config), &rules).await?,
+        None => last_backup_size(config, backup),
+    };
+
+    // Dumps are written to the temporary directory first; a parallel dump holds its
+    // directory and the tarball made from it there at once. Local archives then
+    // land in the storage directory.
+    let temp_needed = match backup.mode {
+        BackupMode::Parallel => estimate.saturating_mul(2),
+        BackupMode::Basic => estimate,
+    };
+    let mut paths = vec![(std::env::temp_dir(), temp_needed)];
+    paths.extend(storage.local_path("").map(|path| (path, estimate)));
+    for (path, needed) in paths {
+        if let Some(available) = available_space(&path)? {
+            if available < needed {
+                anyhow::bail!(
+                    "Not enough free space in {}: {} available, ~{} needed for the next backup",
+                    path.display(), format_size(available), format_size(needed)
+                );
+            }
+        }
+    }
+
     Ok(())
 }
 
+/// Make room for the next backup under the storage quota, returning its estimated size
+async fn enforce_quota(
+    storage: &dyn StorageBackend,
+    backup: &BackupConfig,
+    prefix: &str,
+    rules: &RetentionRules,
+) -> Result<u64> {
+    let Some(quota) = rules.max_total_size else { return Ok(0) };
+    let estimate = job_artifacts(storage, backup, prefix).await?
+        .iter()
+        .max_by_key(|a| a.created)
+        .map_or(0, |a| a.object.size);
+
+    let mut usage = storage_usage(storage).await?;
+    if usage.saturating_add(estimate) > quota {
+        info!(
+            "Storage for '{}' would exceed its {} quota, pruning before backup",
+            backup.name, format_size(quota)
+        );
+        let decisions = plan_retention(storage, backup, prefix, rules, estimate).await?;
+        delete_expired(storage, &decisions).await;
+        usage = storage_usage(storage).await?;
+    }
+    if usage.saturating_add(estimate) > quota {
+        anyhow::bail!(
+            "Storage quota exceeded: {} used plus ~{} for the next backup is over max_total_size {}",
+            format_size(usage), format_size(estimate), format_size(quota)
+        );
+    }
+    Ok(estimate)
+}
+
+/// Size of the job's last successful archive according to the run history, or
+/// 0 (no free-space requirement) when it is unknown
+fn last_backup_size(config: &Config, backup: &BackupConfig) -> u64 {
+    match history::read(config) {
+        Ok(entries) => history::job_status(&entries, &backup.name)
+            .last_success
+            .and_then(|entry| entry.size)
+            .unwrap_or(0),
+        Err(e) => {
+            warn!("Could not estimate the size of '{}' from the run history: {:#}", backup.name, e);
+            0
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{BackupMode, ConnectionConfig};
+    use crate::manifest::{ArchiveDigest, BackupManifest};
+    use crate::storage::LocalStorage;
+    use chrono::{NaiveDate, Utc};
+
+    fn create_test_backup(name: &str) -> BackupConfig {
+        BackupConfig {
+            name: name.to_string(),
+            driver: "postgresql".to_string(),
+            connection: ConnectionConfig {
+                uri: None,
+                host: "localhost".to_string(),
+                port: 5432,
+                username: "postgres".to_string(),
+                password: "password".to_string(),
+                database: "testdb".to_string(),
+            },
+            schedule: None,
+            storage: None,
+            mode: BackupMode::Basic,
+            parallel_jobs: 2,
+            binary_path: None,
+            group: None,
+            timeout: None,
+            retry: None,
+            retention: Some(RetentionConfig::MaxAge("1d".to_string())),
+            verify: None,
+        }
+    }
+
+    fn daily_candidates(days: u32) -> Vec<RetentionCandidate> {
+        let start = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap().and_hms_opt(2, 0, 0).unwrap();
+        (0..days)
+            .map(|day| RetentionCandidate { created: start + chrono::Duration::days(day as i64), size: 1024, good: true })
+            .collect()
+    }
+
+    fn aged_candidates(now: NaiveDateTime, ages_in_days: &[i64]) -> Vec<RetentionCandidate> {
+        ages_in_days.iter()
+            .map(|days| RetentionCandidate { created: now - chrono::Duration::days(*days), size: 1024, good: true })
+            .collect()
+    }
+
+    fn march_first() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 3, 1).unwrap().and_hms_opt(2, 0, 0).unwrap()
+    }
+
+    fn kept(verdicts: &[Verdict], candidates: &[RetentionCandidate]) -> Vec<String> {
+        verdicts.iter()
+            .zip(candidates)
+            .filter(|(v, _)| v.keep)
+            .map(|(_, c)| c.created.format("%Y-%m-%d").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_retention_config_shorthand_and_policy() {
+        let config: RetentionConfig = serde_yaml::from_str("\"30d\"").unwrap();
+        let rules = RetentionRules::from_config(&config).unwrap();
+        assert_eq!(rules.max_age, Some(Duration::from_secs(30 * 86400)));
+        assert_eq!(rules.keep_daily, 0);
+
+        let config: RetentionConfig = serde_yaml::from_str("keep_daily: 7\nkeep_monthly: 12").unwrap();
+        let rules = RetentionRules::from_config(&config).unwrap();
+        assert_eq!(rules.max_age, None);
+        assert_eq!((rules.keep_daily, rules.keep_monthly), (7, 12));
+
+        let config: RetentionConfig = serde_yaml::from_str("max_age: soon").unwrap();
+        assert!(RetentionRules::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_select_gfs() {
+        // One backup a day for all of 2025 and January 2026
+        let candidates = daily_candidates(396);
+        let rules = RetentionRules { keep_daily: 3, keep_monthly: 3, keep_yearly: 2, min_keep: 1, ..Default::default() };
+        let verdicts = select(&rules, &candidates, march_first());
+
+        assert_eq!(kept(&verdicts, &candidates), vec![
+            "2025-11-30", "2025-12-31", "2026-01-29", "2026-01-30", "2026-01-31",
+        ]);
+        let newest = verdicts.last().unwrap();
+        assert_eq!(newest.reason, "keep_daily (2026-01-31), keep_monthly (2026-01), keep_yearly (2026)");
+        assert_eq!(verdicts[0].reason, "not selected by any keep rule");
+    }
+
+    #[test]
+    fn test_select_keep_last_and_weekly() {
+        let candidates = daily_candidates(30);
+        let rules = RetentionRules { keep_last: 2, keep_weekly: 2, ..Default::default() };
+        let verdicts = select(&rules, &candidates, march_first());
+
+        // 2025-01-30 is a Thursday; the newest backup of the week before is Sunday 2025-01-26
+        assert_eq!(kept(&verdicts, &candidates), vec!["2025-01-26", "2025-01-29", "2025-01-30"]);
+    }
+
+    #[test]
+    fn test_select_max_age_with_keep_rules() {
+        let now = march_first();
+        let candidates = aged_candidates(now, &[40, 20, 1]);
+
+        let rules = RetentionRules { max_age: Some(Duration::from_secs(30 * 86400)), ..Default::default() };
+        let verdicts = select(&rules, &candidates, now);
+        assert_eq!(verdicts.iter().map(|v| v.keep).collect::<Vec<_>>(), vec![false, true, true]);
+        assert_eq!(verdicts[0].reason, "older than max_age");
+
+        // A keep rule overrides max_age for the archives it selects
+        let rules = RetentionRules { keep_daily: 3, ..rules };
+        let verdicts = select(&rules, &candidates, now);
+        assert!(verdicts.iter().all(|v| v.keep));
+        assert_eq!(verdicts[0].reason, "keep_daily (2025-01-20)");
+
+        assert!(select(&RetentionRules::default(), &candidates, now).iter().all(|v| v.keep));
+
+        // A max_age beyond the calendar range expires nothing instead of overflowing
+        let rules = RetentionRules { max_age: Some(Duration::from_secs(u64::MAX)), ..Default::default() };
+        assert!(select(&rules, &candidates, now).iter().all(|v| v.keep));
+    }
+
+    #[test]
+    fn test_select_min_keep_floor() {
+        // Backups stopped two weeks ago: a 7 day policy would delete everything
+        let now = march_first();
+        let mut candidates = aged_candidates(now, &[20, 19, 18, 17, 16]);
+        // The newest archive is truncated and does not count as a good backup
+        candidates[4].good = false;
+
+        let rules = RetentionRules { max_age: Some(Duration::from_secs(7 * 86400)), min_keep: 2, ..Default::default() };
+        let verdicts = select(&rules, &candidates, now);
+        assert_eq!(verdicts.iter().map(|v| v.keep).collect::<Vec<_>>(), vec![false, false, true, true, false]);
+        assert_eq!(verdicts[3].reason, "min_keep #1");
+        assert_eq!(verdicts[2].reason, "min_keep #2");
+
+        let rules = RetentionRules { min_keep: 0, ..rules };
+        assert!(select(&rules, &candidates, now).iter().all(|v| !v.keep));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("200GB").unwrap(), 200_000_000_000);
+        assert_eq!(parse_size("1.5 GiB").unwrap(), 3 << 29);
+        assert_eq!(parse_size("512M").unwrap(), 512_000_000);
+        assert_eq!(parse_size("512MiB").unwrap(), 512 << 20);
+        assert_eq!(parse_size("1024").unwrap(), 1024);
+        assert_eq!(parse_size("18446744073709551615").unwrap(), u64::MAX);
+        assert!(parse_size("20000000TB").is_err());
+        assert!(parse_size("").is_err());
+        assert!(parse_size("lots").is_err());
+        assert!(parse_size("10 PB").is_err());
+    }
+
+    #[test]
+    fn test_apply_quota() {
+        let now = march_first();
+        // Five 1 KiB archives plus 1 KiB of unrelated files
+        let candidates = aged_candidates(now, &[5, 4, 3, 2, 1]);
+        let rules = RetentionRules { max_total_size: Some(4096), min_keep: 2, ..Default::default() };
+        let mut verdicts = select(&rules, &candidates, now);
+
+        let projected = apply_quota(&rules, &candidates, &mut verdicts, 6144, 0);
+        assert_eq!(projected, 4096);
+        assert_eq!(verdicts.iter().map(|v| v.keep).collect::<Vec<_>>(), vec![false, false, true, true, true]);
+        assert_eq!(verdicts[0].reason, "over max_total_size (6.0 KiB of 4.0 KiB used)");
+
+        // Reserving room for the next backup frees more, but never the min_keep newest
+        let mut verdicts = select(&rules, &candidates, now);
+        let projected = apply_quota(&rules, &candidates, &mut verdicts, 6144, 4096);
+        assert_eq!(projected, 3072);
+        assert_eq!(verdicts.iter().map(|v| v.keep).collect::<Vec<_>>(), vec![false, false, false, true, true]);
+    }
+
+    #[tokio::test]
+    async fn test_retention_only_touches_own_artifacts() {
+        let root = std::env::temp_dir().join(format!("dbackup_retention_test_{}", uuid::Uuid::new_v4()));
+        let storage_config = StorageConfig {
+            driver: "local".to_string(),
+            path: Some(root.clone()),
+            filename_prefix: None,
+            bucket: None,
+            region: None,
+            prefix: None,
+            endpoint: None,
+            access_key_id: None,
+            secret_access_key: None,
+            max_total_size: None,
+        };
+        let storage = LocalStorage::new(&storage_config).unwrap();
+        let ours = create_test_backup("ours");
+        let theirs = create_test_backup("theirs");
+        let digest = |size| ArchiveDigest { sha256: String::new(), size };
+        let two_days_ago = Utc::now() - chrono::Duration::days(2);
+
+        // Files are freshly written, so their mtimes say nothing about backup age
+        let write = |name: &str, content: &[u8]| std::fs::write(root.join(name), content).unwrap();
+
+        // Our old, complete archive: expired, but the newest good one, so min_keep keeps it
+        write("backup_20260101_020000.dump.gz", b"complete");
+        BackupManifest::new(&ours, "backup_20260101_020000.dump.gz", digest(8), two_days_ago, None)
+            .write_to_dir(&root).unwrap();
+        // A legacy archive dated only by its name, and an expired truncated one
+        write("backup_20260102_020000.dump.gz", b"legacy");
+        write("backup_20260106_020000.dump.gz", b"trunc");
+        BackupManifest::new(&ours, "backup_20260106_020000.dump.gz", digest(4096), two_days_ago, None)
+            .write_to_dir(&root).unwrap();
+        // Renamed or copied archive: the manifest's start time wins over the name.
+        // It is truncated too, so it stays only because it is within max_age.
+        write("backup_20260105_020000.dump.gz", b"trunc");
+        BackupManifest::new(&ours, "backup_20260105_020000.dump.gz", digest(4096), Utc::now(), None)
+            .write_to_dir(&root).unwrap();
+        // Another job's archive in the same location
+        write("backup_20260103_020000.dump.gz", b"");
+        BackupManifest::new(&theirs, "backup_20260103_020000.dump.gz", digest(0), two_days_ago, None)
+            .write_to_dir(&root).unwrap();
+        // Unrelated files, including a MySQL dump and a README
+        write("backup_20260104_020000.sql.gz", b"");
+        write("README.md", b"");
+
+        let rules = RetentionRules::from_config(ours.retention.as_ref().unwrap()).unwrap();
+        assert_eq!(rules.min_keep, DEFAULT_MIN_KEEP);
+        let decisions = plan_retention(&storage, &ours, "backup_", &rules, 0).await.unwrap();
+        let rescued = decisions.iter().find(|d| d.key == "backup_20260101_020000.dump.gz").unwrap();
+        assert_eq!(rescued.verdict.reason, "min_keep #1");
+        apply_retention(&storage, &ours, "backup_", &rules).await.unwrap();
+
+        let mut remaining: Vec<String> = std::fs::read_dir(&root).unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        remaining.sort();
+        assert_eq!(remaining, vec![
+            "README.md",
+            "backup_20260101_020000.dump.gz",
+            "backup_20260101_020000.dump.gz.manifest.json",
+            "backup_20260103_020000.dump.gz",
+            "backup_20260103_020000.dump.gz.manifest.json",
+            "backup_20260104_020000.sql.gz",
+            "backup_20260105_020000.dump.gz",
+            "backup_20260105_020000.dump.gz.manifest.json",
+        ]);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
     
+    #[tokio::test]
+    async fn test_preflight_without_quota_uses_history() {
+        use crate::history::{HistoryEntry, Trigger};
+        use crate::runner::CompletedBackup;
+
+        let root = std::env::temp_dir().join(format!("dbackup_preflight_test_{}", uuid::Uuid::new_v4()));
+        let config: Config = serde_yaml::from_str(&format!(
+            "settings: {{state_dir: {}}}\nbackups: []",
+            root.join("state").display()
+        ))
+        .unwrap();
+        let storage_config: StorageConfig =
+            serde_yaml::from_str(&format!("driver: local\npath: {}", root.join("backups").display())).unwrap();
+        let mut backup = create_test_backup("ours");
+        backup.retention = None;
+
+        // Nothing known about the job yet: no free space is required
+        preflight(&config, &backup, &storage_config).await.unwrap();
+
+        let completed = CompletedBackup { location: "elsewhere".to_string(), size: u64::MAX / 4 };
+        history::record(&config, &HistoryEntry::new("ours", Trigger::Manual, Utc::now(), &Ok(completed))).unwrap();
+        let err = preflight(&config, &backup, &storage_config).await.unwrap_err();
+        assert!(err.to_string().starts_with("Not enough free space"), "{}", err);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
     #[test]
     fn test_parse_seconds() {
         assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
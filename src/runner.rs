@@ -0,0 +1,586 @@
+use crate::artifact::{self, PARALLEL_EXTENSION, TIMESTAMP_FORMAT};
+use crate::config::{BackupConfig, BackupMode, Config, RetryPhase, StorageConfig};
+use crate::driver::{self, BackupDriver};
+use crate::fs_utils::move_file_with_fallback;
+use crate::manifest::{self, ArchiveDigest, BackupManifest, HashingWriter};
+use crate::restore;
+use crate::retention;
+use crate::storage::{self, StorageBackend};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::process::{Child, Command};
+use tracing::{info, warn};
+
+/// Read size when streaming a dump into the gzip encoder
+const DUMP_CHUNK_SIZE: usize = 64 * 1024;
+
+/// A stored backup archive
+#[derive(Debug, Clone)]
+pub struct CompletedBackup {
+    pub location: String,
+    pub size: u64,
+}
+
+/// Run one backup job end to end: connection check, pre-flight, dump and retention.
+pub async fn run_backup_job(config: &Config, backup: &BackupConfig) -> Result<CompletedBackup> {
+    let storage_config = config.get_storage_for_backup(backup)
+        .context(format!("Failed to resolve storage for backup '{}'", backup.name))?;
+    let driver = driver::create_driver(backup)?;
+
+    // Validate connection before attempting backup
+    driver.validate_connection()
+        .context("Connection validation failed")?;
+    let timeout = backup.timeout()?;
+
+    // Make sure the backup fits before starting the dump
+    retention::preflight(config, backup, &storage_config)
+        .await
+        .context("Pre-flight check failed")?;
+
+    // Dropping a timed-out backup kills the dump's process group and removes its temp dir
+    let sql_client = config.sql_client_binary(driver.as_ref());
+    let run = execute(driver.as_ref(), backup, &storage_config, sql_client.as_deref());
+    let completed = match (timeout, &backup.timeout) {
+        (Some(limit), Some(label)) => tokio::time::timeout(limit, run)
+            .await
+            .map_err(|_| anyhow::anyhow!("Backup timed out after {}", label))??,
+        _ => run.await?,
+    };
+
+    retention::enforce(backup, &storage_config).await;
+    Ok(completed)
+}
+
+/// Check that a backup job's storage, database connection and retention policy are usable
+pub async fn validate_backup_job(config: &Config, backup: &BackupConfig) -> Result<()> {
+    let storage_config = config.get_storage_for_backup(backup)
+        .context(format!("Failed to resolve storage for backup '{}'", backup.name))?;
+
+    // Validate storage connection
+    info!("  Testing {} storage connection...", storage_config.driver);
+    storage::create_storage(&storage_config)
+        .await
+        .context(format!("Storage validation failed for backup '{}'", backup.name))?;
+    info!("  ✓ {} storage validated", storage_config.driver);
+
+    // Validate database connection
+    let driver = driver::create_driver(backup)?;
+    driver.validate_connection()
+        .context(format!("Database validation failed for backup '{}'", backup.name))?;
+    info!("  ✓ {} connection validated", driver.info().display_name);
+
+    backup.timeout()?;
+    RetryPolicy::for_backup(backup)?;
+
+    // Validate retention policy
+    if retention::RetentionRules::for_backup(backup, &storage_config)
+        .context(format!("Invalid retention policy for backup '{}'", backup.name))?
+        .is_some()
+    {
+        info!("  ✓ Retention policy validated");
+    }
+
+    Ok(())
+}
+
+/// Temporary directory for one backup run, removed when dropped
+struct BackupWorkspace {
+    path: PathBuf,
+}
+
+impl BackupWorkspace {
+    fn new() -> Result<Self> {
+        let path = std::env::temp_dir().join(format!("dbackup_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&path)
+            .context("Failed to create temporary directory")?;
+        Ok(Self { path })
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for BackupWorkspace {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Dump one archive in a scratch directory and hand it and its manifest to storage
+async fn execute(
+    driver: &dyn BackupDriver,
+    backup: &BackupConfig,
+    storage_config: &StorageConfig,
+    sql_client: Option<&Path>,
+) -> Result<CompletedBackup> {
+    info!("Starting {} backup for: {}", driver.info().display_name, backup.name);
+
+    let started_at = Utc::now();
+    let basename = format!(
+        "{}{}",
+        artifact::filename_prefix(storage_config),
+        Local::now().format(TIMESTAMP_FORMAT)
+    );
+
+    // Create storage backend
+    let storage = storage::create_storage(storage_config).await?;
+
+    // Scratch space for the dump; removed on success, failure or cancellation
+    let workspace = BackupWorkspace::new()?;
+
+    let completed = dump_and_store(driver, backup, storage.as_ref(), workspace.path(), &basename, started_at, sql_client).await?;
+    info!("Backup completed successfully: {}", completed.location);
+    Ok(completed)
+}
+
+async fn dump_and_store(
+    driver: &dyn BackupDriver,
+    backup: &BackupConfig,
+    storage: &dyn StorageBackend,
+    temp_dir: &Path,
+    basename: &str,
+    started_at: DateTime<Utc>,
+    sql_client: Option<&Path>,
+) -> Result<CompletedBackup> {
+    let retry = RetryPolicy::for_backup(backup)?;
+
+    let (archive_path, filename, digest) = retry
+        .run(RetryPhase::Dump, || dump(driver, backup, temp_dir, basename))
+        .await?;
+
+    // Record integrity information next to the archive
+    let manifest = BackupManifest::new(backup, &filename, digest, started_at, driver.server_version(sql_client).await);
+    let manifest_path = manifest.write_to_dir(temp_dir)?;
+    info!("Backup SHA-256: {} ({} bytes)", manifest.sha256, manifest.size);
+
+    // A failed upload is retried with the archive already on disk
+    let location = retry
+        .run(RetryPhase::Upload, || store(storage, &archive_path, &filename, &manifest_path))
+        .await?;
+    Ok(CompletedBackup { location, size: manifest.size })
+}
+
+/// Execute the dump for the job's mode, returning the archive path, name and digest
+async fn dump(
+    driver: &dyn BackupDriver,
+    backup: &BackupConfig,
+    temp_dir: &Path,
+    basename: &str,
+) -> Result<(PathBuf, String, ArchiveDigest)> {
+    match backup.mode {
+        BackupMode::Basic => {
+            info!("Using basic mode (single compressed dump)...");
+            dump_stream(driver, temp_dir, basename).await
+        }
+        BackupMode::Parallel => {
+            info!("Using parallel mode (directory dump with {} jobs)...", backup.parallel_jobs);
+            dump_directory(driver, temp_dir, basename).await
+        }
+    }
+}
+
+/// Hand the archive and its manifest to storage, returning the archive location
+async fn store(storage: &dyn StorageBackend, archive_path: &Path, filename: &str, manifest_path: &Path) -> Result<String> {
+    let manifest_filename = manifest::manifest_name(filename);
+
+    // Local storage: move into place; remote storage: upload the archive and its manifest
+    match (storage.local_path(filename), storage.local_path(&manifest_filename)) {
+        (Some(final_path), Some(final_manifest_path)) => {
+            // Skip files a previous attempt already moved
+            if archive_path.exists() {
+                move_file_with_fallback(archive_path, &final_path)
+                    .context("Failed to move backup file to final location")?;
+            }
+            if manifest_path.exists() {
+                move_file_with_fallback(manifest_path, &final_manifest_path)
+                    .context("Failed to move manifest to final location")?;
+            }
+            Ok(final_path.display().to_string())
+        }
+        _ => {
+            let location = storage.store(archive_path, filename).await?;
+            storage.store(manifest_path, &manifest_filename).await?;
+            Ok(location)
+        }
+    }
+}
+
+/// Parsed `retry` block; without one every phase runs once
+pub struct RetryPolicy {
+    attempts: u32,
+    initial_delay: Duration,
+    max_delay: Duration,
+    phases: Vec<RetryPhase>,
+}
+
+impl RetryPolicy {
+    pub fn for_backup(backup: &BackupConfig) -> Result<Self> {
+        let Some(retry) = &backup.retry else {
+            return Ok(Self { attempts: 1, initial_delay: Duration::ZERO, max_delay: Duration::ZERO, phases: Vec::new() });
+        };
+
+        let context = || format!("Invalid retry settings for backup '{}'", backup.name);
+        if retry.attempts == 0 {
+            return Err(anyhow::anyhow!("attempts must be at least 1")).context(context());
+        }
+        Ok(Self {
+            attempts: retry.attempts,
+            initial_delay: retention::parse_duration(&retry.initial_delay).context(context())?,
+            max_delay: retention::parse_duration(&retry.max_delay).context(context())?,
+            phases: retry.phases.clone(),
+        })
+    }
+
+    /// Wait before attempt `attempt + 1`: doubles from `initial_delay`, capped at `max_delay`
+    fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_delay.saturating_mul(factor).min(self.max_delay)
+    }
+
+    /// Run one phase of the backup, retrying it if the policy covers the phase
+    async fn run<T, F, Fut>(&self, phase: RetryPhase, mut operation: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let attempts = if self.phases.contains(&phase) { self.attempts } else { 1 };
+        let name = match phase {
+            RetryPhase::Dump => "Dump",
+            RetryPhase::Upload => "Upload",
+        };
+
+        let mut attempt = 1;
+        loop {
+            match operation().await {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < attempts => {
+                    let delay = self.delay(attempt);
+                    warn!(
+                        "✗ {} failed (attempt {}/{}): {:#}; retrying in {}s",
+                        name, attempt, attempts, e, delay.as_secs()
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) if attempts > 1 => {
+                    return Err(e.context(format!("{} failed after {} attempts", name, attempts)));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Gzip the driver's stdout dump into `<basename><extension>`
+async fn dump_stream(driver: &dyn BackupDriver, temp_dir: &Path, basename: &str) -> Result<(PathBuf, String, ArchiveDigest)> {
+    let tool = driver.info().dump_tool;
+    let filename = format!("{}{}", basename, driver.info().extension);
+    let output_path = temp_dir.join(&filename);
+
+    info!("Backing up to: {}", output_path.display());
+
+    let mut cmd = driver.dump_stream_command();
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+
+    info!("Executing {} with compression...", tool);
+    let (mut child, group) = spawn_dump(cmd, tool)?;
+    let stderr = restore::collect_output(child.stderr.take());
+    let mut stdout = child
+        .stdout
+        .take()
+        .context(format!("Failed to capture {} stdout", tool))?;
+
+    // Create gzip compressed output file
+    let output_file = File::create(&output_path)
+        .context("Failed to create output file")?;
+    let mut encoder = GzEncoder::new(HashingWriter::new(output_file), Compression::best());
+
+    // Compress the dump as it arrives rather than buffering it in memory
+    let mut buffer = vec![0u8; DUMP_CHUNK_SIZE];
+    let streamed: Result<()> = async {
+        loop {
+            let read = stdout.read(&mut buffer)
+                .await
+                .context(format!("Failed to read {} output", tool))?;
+            if read == 0 {
+                break Ok(());
+            }
+            encoder
+                .write_all(&buffer[..read])
+                .context("Failed to write compressed data")?;
+        }
+    }
+    .await;
+    drop(stdout);
+
+    // Wait for the process to complete before judging the output
+    restore::wait_for_client(tool, child, stderr).await?;
+    group.disarm();
+    streamed?;
+
+    let (_, digest) = encoder.finish().context("Failed to finalize compression")?.finish()?;
+    info!("Backup compressed to {}", output_path.display());
+    Ok((output_path, filename, digest))
+}
+
+/// Let the driver dump into a directory, then pack it into `<basename>.dir.tar.gz`
+async fn dump_directory(driver: &dyn BackupDriver, temp_dir: &Path, basename: &str) -> Result<(PathBuf, String, ArchiveDigest)> {
+    let tool = driver.info().dump_tool;
+
+    // Create temporary directory for directory format backup, empty even on a retry
+    let dump_dir = temp_dir.join(basename);
+    if dump_dir.exists() {
+        std::fs::remove_dir_all(&dump_dir)
+            .context("Failed to clear temporary directory")?;
+    }
+    std::fs::create_dir_all(&dump_dir)
+        .context("Failed to create temporary directory")?;
+
+    info!("Using temporary directory: {}", dump_dir.display());
+
+    let mut cmd = driver.dump_directory_command(&dump_dir);
+    cmd.stdout(Stdio::null());
+    cmd.stderr(Stdio::piped());
+
+    info!("Executing {} into directory...", tool);
+    let (mut child, group) = spawn_dump(cmd, tool)?;
+    let stderr = restore::collect_output(child.stderr.take());
+    restore::wait_for_client(tool, child, stderr).await?;
+    group.disarm();
+
+    // Compress the directory into a tar.gz file
+    let tar_filename = format!("{}{}", basename, PARALLEL_EXTENSION);
+    let tar_path = temp_dir.join(&tar_filename);
+
+    info!("Compressing directory backup to {}", tar_path.display());
+    let cancel = CancelOnDrop::default();
+    let cancelled = cancel.0.clone();
+    let (source, output) = (dump_dir.clone(), tar_path.clone());
+    let digest = tokio::task::spawn_blocking(move || compress_directory(&source, &output, &cancelled))
+        .await
+        .context("Compression task failed")??;
+    drop(cancel);
+
+    // Cleanup the dump directory
+    std::fs::remove_dir_all(&dump_dir)
+        .context("Failed to remove temporary directory")?;
+
+    Ok((tar_path, tar_filename, digest))
+}
+
+/// Spawn a dump tool in its own process group.
+///
+/// If the backup is cancelled (dropped) before the tool exits, the whole group
+/// is killed, including workers the tool forked (`pg_dump -j`).
+fn spawn_dump(mut cmd: Command, tool: &str) -> Result<(Child, ProcessGroupGuard)> {
+    #[cfg(unix)]
+    cmd.process_group(0);
+    cmd.kill_on_drop(true);
+
+    let child = cmd.spawn().context(format!("Failed to spawn {} process", tool))?;
+    let group = ProcessGroupGuard { pgid: child.id() };
+    Ok((child, group))
+}
+
+/// Kills a dump's process group when dropped, unless disarmed after the dump exited
+struct ProcessGroupGuard {
+    pgid: Option<u32>,
+}
+
+impl ProcessGroupGuard {
+    fn disarm(mut self) {
+        self.pgid = None;
+    }
+}
+
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(pgid) = self.pgid {
+            // SAFETY: killpg only sends a signal; the group was created for this dump
+            unsafe {
+                libc::killpg(pgid as libc::pid_t, libc::SIGKILL);
+            }
+        }
+    }
+}
+
+/// Signals a blocking compression to stop when the backup is cancelled (dropped),
+/// e.g. by the job timeout, so the worker thread does not keep running
+#[derive(Default)]
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Fails every write once the cancellation flag is set
+struct CancellableWriter<W> {
+    inner: W,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl<W: Write> Write for CancellableWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Err(std::io::Error::other("compression cancelled"));
+        }
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Pack a directory into a tar.gz; blocking, so run it with `spawn_blocking`
+fn compress_directory(source_dir: &Path, output_path: &Path, cancelled: &Arc<AtomicBool>) -> Result<ArchiveDigest> {
+    use tar::Builder;
+
+    let tar_gz = File::create(output_path)
+        .context("Failed to create tar.gz file")?;
+    let output = CancellableWriter { inner: tar_gz, cancelled: cancelled.clone() };
+    let encoder = GzEncoder::new(HashingWriter::new(output), Compression::default());
+    let mut tar = Builder::new(encoder);
+
+    // Add all files from the directory to the tar archive
+    tar.append_dir_all(".", source_dir)
+        .context("Failed to add directory to tar archive")?;
+
+    let encoder = tar.into_inner().context("Failed to finalize tar archive")?;
+    let (_, digest) = encoder.finish().context("Failed to finalize compression")?.finish()?;
+    Ok(digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_backup_timeout_kills_dump() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("dbackup_test_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let hung_dump = dir.join("pg_dump");
+        std::fs::write(&hung_dump, "#!/bin/sh\nsleep 30\n").unwrap();
+        std::fs::set_permissions(&hung_dump, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let config: Config = serde_yaml::from_str(&format!(
+            "backups:\n  - name: hung\n    driver: postgresql\n    binary_path: {}\n    timeout: 1s\n    \
+             connection: {{host: localhost, port: 5432, username: u, password: p, database: db}}\n    \
+             storage: {{driver: local, path: {}}}",
+            hung_dump.display(),
+            dir.join("out").display()
+        ))
+        .unwrap();
+
+        let started = std::time::Instant::now();
+        let err = run_backup_job(&config, &config.backups[0]).await.unwrap_err();
+        assert_eq!(err.to_string(), "Backup timed out after 1s");
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+        assert_eq!(std::fs::read_dir(dir.join("out")).unwrap().count(), 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_retry_failed_dump() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("dbackup_test_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // Fails on the first call, dumps on the second
+        let flaky_dump = dir.join("pg_dump");
+        std::fs::write(
+            &flaky_dump,
+            format!(
+                "#!/bin/sh\nif [ ! -e {0} ]; then touch {0}; exit 1; fi\necho dump\n",
+                dir.join("failed_once").display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&flaky_dump, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let config: Config = serde_yaml::from_str(&format!(
+            "backups:\n  - name: flaky\n    driver: postgresql\n    binary_path: {}\n    \
+             retry: {{attempts: 2, initial_delay: 0s}}\n    \
+             connection: {{host: localhost, port: 5432, username: u, password: p, database: db}}\n    \
+             storage: {{driver: local, path: {}}}",
+            flaky_dump.display(),
+            dir.join("out").display()
+        ))
+        .unwrap();
+
+        run_backup_job(&config, &config.backups[0]).await.unwrap();
+        // Archive plus manifest
+        assert_eq!(std::fs::read_dir(dir.join("out")).unwrap().count(), 2);
+
+        // Without a retry block the same failure is final
+        std::fs::remove_file(dir.join("failed_once")).unwrap();
+        let mut backup = config.backups[0].clone();
+        backup.retry = None;
+        assert!(run_backup_job(&config, &backup).await.is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let backup: BackupConfig = serde_yaml::from_str(
+            "name: test\ndriver: postgresql\nretry: {attempts: 5, initial_delay: 30s, max_delay: 2m, phases: [upload]}\n\
+             connection: {host: localhost, port: 5432, username: u, password: p, database: db}",
+        )
+        .unwrap();
+        let policy = RetryPolicy::for_backup(&backup).unwrap();
+        assert_eq!(policy.phases, vec![RetryPhase::Upload]);
+        let delays: Vec<u64> = (1..5).map(|attempt| policy.delay(attempt).as_secs()).collect();
+        assert_eq!(delays, vec![30, 60, 120, 120]);
+
+        let mut invalid = backup.clone();
+        invalid.retry.as_mut().unwrap().attempts = 0;
+        assert!(RetryPolicy::for_backup(&invalid).is_err());
+        invalid.retry.as_mut().unwrap().attempts = 3;
+        invalid.retry.as_mut().unwrap().max_delay = "soon".to_string();
+        assert!(RetryPolicy::for_backup(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_compress_directory() {
+        let dir = std::env::temp_dir().join(format!("dbackup_test_{}", uuid::Uuid::new_v4()));
+        let source = dir.join("dump");
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::write(source.join("toc.dat"), b"table of contents").unwrap();
+
+        let archive = dir.join("backup.dir.tar.gz");
+        let digest = compress_directory(&source, &archive, &Arc::default()).unwrap();
+        assert_eq!(digest.size, std::fs::metadata(&archive).unwrap().len());
+
+        // A cancelled compression stops instead of producing an archive
+        let cancelled = Arc::new(AtomicBool::new(true));
+        assert!(compress_directory(&source, &dir.join("cancelled.dir.tar.gz"), &cancelled).is_err());
+
+        let extracted = dir.join("extracted");
+        restore::extract_tarball(&archive, &extracted).unwrap();
+        assert_eq!(std::fs::read(extracted.join("toc.dat")).unwrap(), b"table of contents");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
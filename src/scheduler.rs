@@ -1,168 +1,906 @@
-use crate::config::{BackupConfig, Config};
-use crate::postgres::PostgresBackup;
+use crate::config::{BackupConfig, CatchUp, Config, ConnectionConfig, Overlap, ScheduleConfig};
+use crate::driver;
+use crate::history::{self, HistoryEntry, Trigger};
+use crate::lock::{self, LockOutcome};
+use crate::retention::{self, RetentionRules};
+use crate::runner;
+use crate::state::{RunKind, RunState, StateStore};
+use crate::verify;
 use anyhow::{Context, Result};
-use chrono::Local;
+use chrono::{DateTime, Local, Utc};
 use cron::Schedule;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::str::FromStr;
-use tokio::sync::Semaphore;
-use tokio::task::JoinHandle;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{watch, OwnedSemaphorePermit, Semaphore};
+use tokio::task::{JoinHandle, JoinSet};
 use tracing::{error, info, warn};
 
+/// How often `--watch` checks the configuration file for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
 pub struct BackupScheduler {
+    config_path: PathBuf,
     config: Config,
     semaphore: std::sync::Arc<Semaphore>,
     max_concurrent: usize,
+    watch_file: bool,
+    grace_period: Duration,
+    state: std::sync::Arc<StateStore>,
+    /// Slots of each concurrency group with the limit they were created for
+    groups: HashMap<String, (usize, std::sync::Arc<Semaphore>)>,
+    /// Set once the shutdown grace period is over: every task aborts its runs
+    abort: watch::Sender<bool>,
+    /// Unscheduled tasks that may still be finishing a run
+    retired: Vec<(TaskKey, JoinHandle<()>)>,
+}
+
+/// A scheduled task derived from the configuration
+struct TaskSpec {
+    backup: BackupConfig,
+    schedule: ScheduleConfig,
+    /// Concurrency group the task's runs count against
+    group: String,
+    /// Everything the task depends on; a task is restarted when this changes
+    fingerprint: String,
+}
+
+struct RunningTask {
+    fingerprint: String,
+    stop: watch::Sender<bool>,
+    handle: JoinHandle<()>,
+}
+
+/// What a task listens to besides its schedule
+struct TaskSignals {
+    /// Stop scheduling; runs already in flight finish
+    stop: watch::Receiver<bool>,
+    /// Abort the runs in flight
+    abort: watch::Receiver<bool>,
 }
 
+type TaskKey = (String, RunKind);
+
 impl BackupScheduler {
-    pub fn new(config: Config, max_concurrent: usize) -> Self {
+    pub fn new(
+        config_path: PathBuf,
+        config: Config,
+        max_concurrent: usize,
+        watch_file: bool,
+        grace_period: Duration,
+    ) -> Self {
         Self {
+            state: std::sync::Arc::new(StateStore::open(&config.state_dir())),
+            config_path,
             config,
             semaphore: std::sync::Arc::new(Semaphore::new(max_concurrent)),
             max_concurrent,
+            watch_file,
+            grace_period,
+            groups: HashMap::new(),
+            abort: watch::channel(false).0,
+            retired: Vec::new(),
         }
     }
 
-    pub async fn run(&self) -> Result<()> {
+    pub async fn run(mut self) -> Result<()> {
+        check_config(&self.config)?;
+
         info!("Starting backup scheduler with {} concurrent slots", self.max_concurrent);
+        #[cfg(unix)]
+        info!("Send SIGHUP to reload {}", self.config_path.display());
+        if self.watch_file {
+            info!("Watching {} for changes", self.config_path.display());
+        }
         info!("Press Ctrl+C to stop");
 
-        // Collect all scheduled backups
-        let scheduled_backups: Vec<_> = self.config.backups
-            .iter()
-            .filter(|b| b.schedule.is_some())
-            .collect();
-
-        if scheduled_backups.is_empty() {
+        let mut running: HashMap<TaskKey, RunningTask> = HashMap::new();
+        self.apply(&mut running);
+        if running.is_empty() {
             warn!("No scheduled backups found in configuration");
-            return Ok(());
         }
 
-        info!("Found {} scheduled backup(s)", scheduled_backups.len());
+        #[cfg(unix)]
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+            .context("Failed to install SIGHUP handler")?;
+        let mut shutdown = ShutdownSignal::new()?;
+        let mut last_modified = modified_time(&self.config_path);
+        let mut poll = tokio::time::interval(WATCH_INTERVAL);
+
+        loop {
+            #[cfg(unix)]
+            let hangup = hangup.recv();
+            #[cfg(not(unix))]
+            let hangup = std::future::pending::<Option<()>>();
+
+            tokio::select! {
+                signal = shutdown.recv() => {
+                    info!("Received {}, shutting down", signal);
+                    return self.shutdown(running, &mut shutdown).await;
+                }
+                _ = hangup => {
+                    info!("Received SIGHUP, reloading configuration");
+                }
+                _ = poll.tick(), if self.watch_file => {
+                    let modified = modified_time(&self.config_path);
+                    if modified == last_modified {
+                        continue;
+                    }
+                    info!("Configuration file changed, reloading");
+                }
+            }
 
-        // Print schedule information
-        for backup in &scheduled_backups {
-            if let Some(schedule) = &backup.schedule {
-                info!("  - '{}' scheduled for: {}", backup.name, schedule.cron);
+            last_modified = modified_time(&self.config_path);
+            match self.reload() {
+                Ok(()) => self.apply(&mut running),
+                Err(e) => error!("✗ Reload failed, keeping the current configuration: {:#}", e),
             }
         }
+    }
 
-        let mut handles: Vec<JoinHandle<Result<()>>> = Vec::new();
+    /// Stop scheduling, let running backups finish within the grace period and abort the rest.
+    ///
+    /// Aborting drops a task's in-flight runs: their dump processes are killed and
+    /// temporary directories removed before the task returns. A second signal
+    /// aborts immediately.
+    async fn shutdown(&mut self, running: HashMap<TaskKey, RunningTask>, signal: &mut ShutdownSignal) -> Result<()> {
+        for task in running.values() {
+            let _ = task.stop.send(true);
+        }
+        // Unscheduled tasks were stopped already but get the same grace period
+        let tasks: Vec<(TaskKey, JoinHandle<()>)> = running
+            .into_iter()
+            .map(|(key, task)| (key, task.handle))
+            .chain(std::mem::take(&mut self.retired))
+            .collect();
 
-        // Spawn a task for each scheduled backup
-        for backup_config in self.config.backups.iter() {
-            if let Some(schedule) = &backup_config.schedule {
-                let backup = backup_config.clone();
-                let cron_expr = schedule.cron.clone();
-                let semaphore = self.semaphore.clone();
-                let config = self.config.clone();
+        let busy = self.max_concurrent - self.semaphore.available_permits();
+        if busy > 0 {
+            info!(
+                "Waiting up to {}s for {} running task(s) to finish (signal again to abort now)",
+                self.grace_period.as_secs(),
+                busy
+            );
+        }
 
-                let handle = tokio::spawn(Self::run_scheduled_backup(
-                    backup,
-                    cron_expr,
-                    semaphore,
-                    config,
-                ));
+        let deadline = tokio::time::Instant::now() + self.grace_period;
+        let mut force = false;
+        let mut unfinished = Vec::new();
+        for ((name, kind), mut handle) in tasks {
+            if !force {
+                tokio::select! {
+                    _ = &mut handle => continue,
+                    _ = tokio::time::sleep_until(deadline) => {}
+                    signal = signal.recv() => {
+                        warn!("Received {} again, aborting running tasks", signal);
+                        force = true;
+                    }
+                }
+            }
+            if !handle.is_finished() {
+                unfinished.push((name, kind, handle));
+            }
+        }
+
+        let _ = self.abort.send(true);
+        let mut aborted = Vec::new();
+        for (name, kind, handle) in unfinished {
+            // Tasks return once their aborted runs have been dropped
+            let _ = handle.await;
+            warn!("✗ Aborted {} '{}'", describe(kind), name);
+            aborted.push(name);
+        }
+
+        if aborted.is_empty() {
+            info!("✓ Scheduler stopped");
+            Ok(())
+        } else {
+            anyhow::bail!("Shutdown aborted {} running task(s): {}", aborted.len(), aborted.join(", "))
+        }
+    }
 
-                handles.push(handle);
+    /// Read and check the configuration file, replacing the active config only if it is valid
+    fn reload(&mut self) -> Result<()> {
+        let config = Config::from_file(&self.config_path)
+            .context(format!("Failed to load configuration file: {}", self.config_path.display()))?;
+        check_config(&config)?;
+
+        // History and locks follow the new directory, so scheduler state must too.
+        // Every task depends on the settings and is restarted with the new store.
+        let state_dir = config.state_dir();
+        if state_dir != self.config.state_dir() {
+            info!("State directory changed to {}, reopening scheduler state", state_dir.display());
+            self.state = std::sync::Arc::new(StateStore::open(&state_dir));
+        }
+        self.config = config;
+        Ok(())
+    }
+
+    /// Start, stop and restart tasks so they match the active config.
+    ///
+    /// Unchanged tasks keep their timers. A stopped task finishes a run that is
+    /// already in progress; its replacement waits for that before scheduling.
+    fn apply(&mut self, running: &mut HashMap<TaskKey, RunningTask>) {
+        let mut desired = match scheduled_tasks(&self.config) {
+            Ok(tasks) => tasks,
+            Err(e) => {
+                error!("✗ Could not schedule tasks: {:#}", e);
+                return;
+            }
+        };
+
+        let keys: Vec<TaskKey> = running.keys().cloned().collect();
+        for key in keys {
+            let unchanged = desired.get(&key).is_some_and(|spec| spec.fingerprint == running[&key].fingerprint);
+            if unchanged {
+                desired.remove(&key);
+            } else if !desired.contains_key(&key) {
+                let task = running.remove(&key).expect("task key was just listed");
+                info!("Unscheduling {} '{}'", describe(key.1), key.0);
+                let _ = task.stop.send(true);
+                self.retired.push((key, task.handle));
             }
         }
+        self.retired.retain(|(_, handle)| !handle.is_finished());
 
-        // Wait for all tasks (they run indefinitely until interrupted)
-        for handle in handles {
-            if let Err(e) = handle.await {
-                error!("Scheduler task panicked: {}", e);
+        for (key, spec) in desired {
+            let previous = running.remove(&key).map(|task| {
+                info!("Rescheduling {} '{}'", describe(key.1), key.0);
+                let _ = task.stop.send(true);
+                task.handle
+            });
+            if previous.is_none() {
+                info!(
+                    "  - '{}' {} scheduled for: {} (group: {})",
+                    key.0, describe(key.1), spec.schedule.cron, spec.group
+                );
             }
+
+            let (stop, stopped) = watch::channel(false);
+            let signals = TaskSignals { stop: stopped, abort: self.abort.subscribe() };
+            let fingerprint = spec.fingerprint.clone();
+            let handle = tokio::spawn(Self::run_task(
+                key.1,
+                self.slots(&spec.group),
+                spec,
+                self.config.clone(),
+                self.state.clone(),
+                signals,
+                previous,
+            ));
+            running.insert(key, RunningTask { fingerprint, stop, handle });
         }
+    }
 
-        Ok(())
+    /// Slots for a run in `group`, resized in place when the group's limit changed
+    /// so runs still holding a slot keep counting against the new limit
+    fn slots(&mut self, group: &str) -> Slots {
+        let limit = self.config.group_limit(group);
+        let entry = self
+            .groups
+            .entry(group.to_string())
+            .or_insert_with(|| (limit, std::sync::Arc::new(Semaphore::new(limit))));
+        if limit > entry.0 {
+            entry.1.add_permits(limit - entry.0);
+        } else if limit < entry.0 {
+            let excess = entry.0 - limit;
+            let held = excess - entry.1.forget_permits(excess);
+            if held > 0 {
+                // The rest are held by running jobs; retire them as those finish
+                let semaphore = entry.1.clone();
+                tokio::spawn(async move {
+                    if let Ok(permits) = semaphore.acquire_many_owned(held as u32).await {
+                        permits.forget();
+                    }
+                });
+            }
+        }
+        entry.0 = limit;
+        Slots { group: group.to_string(), group_slots: entry.1.clone(), global: self.semaphore.clone() }
     }
 
-    async fn run_scheduled_backup(
-        backup: BackupConfig,
-        cron_expr: String,
-        semaphore: std::sync::Arc<Semaphore>,
+    async fn run_task(
+        kind: RunKind,
+        slots: Slots,
+        spec: TaskSpec,
         config: Config,
-    ) -> Result<()> {
-        let schedule = Schedule::from_str(&cron_expr)
-            .context(format!("Invalid cron expression: {}", cron_expr))?;
+        state: std::sync::Arc<StateStore>,
+        signals: TaskSignals,
+        previous: Option<JoinHandle<()>>,
+    ) {
+        // Never let the old and new version of a job run at the same time
+        if let Some(previous) = previous {
+            let _ = previous.await;
+        }
 
-        info!("Scheduled backup '{}' initialized with cron: {}", backup.name, cron_expr);
+        let name = spec.backup.name.clone();
+        let config = std::sync::Arc::new(config);
+        let backup = std::sync::Arc::new(spec.backup);
+        let run = {
+            let state = state.clone();
+            move |scheduled_at: DateTime<Utc>| -> RunFuture {
+                match kind {
+                    RunKind::Backup => {
+                        Box::pin(Self::run_backup(config.clone(), backup.clone(), state.clone(), scheduled_at))
+                    }
+                    RunKind::Verification => {
+                        Box::pin(Self::run_verification(config.clone(), backup.clone(), state.clone()))
+                    }
+                }
+            }
+        };
 
-        loop {
-            // Get next run time using iterator
-            let now = Local::now();
-            
-            // The cron schedule iterator gives us upcoming times
-            let mut upcoming = schedule.after(&now);
-            if let Some(next_run) = upcoming.next() {
-                // Calculate sleep duration
-                let sleep_duration = (next_run - now).to_std()?;
+        if let Err(e) = Self::run_schedule(&name, kind, &spec.schedule, slots, &state, signals, run).await {
+            error!("Scheduler task for '{}' stopped: {:#}", name, e);
+        }
+    }
 
-                info!(
-                    "Next run for '{}': {} (in {:.0}s)",
-                    backup.name,
-                    next_run.format("%Y-%m-%d %H:%M:%S"),
-                    sleep_duration.as_secs_f64()
-                );
+    /// Start runs at the scheduled times until stopped, applying the overlap policy
+    /// when a time comes up while an earlier run is still going. In-flight runs are
+    /// awaited before returning, or aborted and drained once `abort` is set.
+    async fn run_schedule(
+        name: &str,
+        kind: RunKind,
+        schedule_config: &ScheduleConfig,
+        slots: Slots,
+        state: &StateStore,
+        signals: TaskSignals,
+        run: impl Fn(DateTime<Utc>) -> RunFuture,
+    ) -> Result<()> {
+        let TaskSignals { mut stop, mut abort } = signals;
+        let cron_expr = &schedule_config.cron;
+        let schedule = parse_cron(cron_expr)?;
 
-                // Sleep until next run
-                tokio::time::sleep(sleep_duration).await;
-
-                // Acquire semaphore permit (limits concurrent backups)
-                let _permit = semaphore.acquire().await?;
-
-                info!("Starting scheduled backup: {}", backup.name);
-
-                // Execute backup
-                match &backup.driver.as_str() {
-                    &"postgresql" => {
-                        // Resolve storage configuration
-                        match config.get_storage_for_backup(&backup) {
-                            Ok(storage_config) => {
-                                let backup_executor = PostgresBackup::new(backup.clone(), storage_config);
-
-                                // Validate connection first
-                                if let Err(e) = backup_executor.validate_connection() {
-                                    error!("Connection validation failed for '{}': {}", backup.name, e);
-                                    continue;
-                                }
-
-                                // Run backup
-                                match backup_executor.execute().await {
-                                    Ok(location) => {
-                                        info!(
-                                            "✓ Scheduled backup '{}' completed: {}",
-                                            backup.name,
-                                            location
-                                        );
-                                    }
-                                    Err(e) => {
-                                        error!("✗ Scheduled backup '{}' failed: {}", backup.name, e);
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                error!("Failed to resolve storage for '{}': {}", backup.name, e);
+        let what = match kind {
+            RunKind::Backup => {
+                info!("Scheduled backup '{}' initialized with cron: {}", name, cron_expr);
+                "run"
+            }
+            RunKind::Verification => {
+                info!("Scheduled verification for '{}' initialized with cron: {}", name, cron_expr);
+                "verification"
+            }
+        };
+        let mut catch_up = Self::check_catch_up(name, kind, schedule_config, &schedule, state)?;
+
+        let mut runs = JoinSet::new();
+        // Scheduled time of the run waiting for the current one to finish
+        let mut queued: Option<DateTime<Utc>> = None;
+        loop {
+            tokio::select! {
+                due = Self::wait_for_run(name, what, &schedule, &mut catch_up, &mut stop) => {
+                    let Some(due) = due? else {
+                        break;
+                    };
+                    let due = due.with_timezone(&Utc);
+                    match overlap_action(schedule_config.overlap, runs.len(), queued.is_some()) {
+                        OverlapAction::Start => {
+                            if !runs.is_empty() {
+                                warn!("Previous {} of '{}' is still running, starting another (overlap: allow)", describe(kind), name);
                             }
+                            Self::start_run(name, &mut runs, run(due), &slots, &stop);
+                        }
+                        OverlapAction::Queue => {
+                            queued = Some(due);
+                            let total = state.record_queued(kind, name);
+                            warn!(
+                                "Previous {} of '{}' is still running, queued the next one ({} queued so far)",
+                                describe(kind), name, total
+                            );
+                        }
+                        OverlapAction::Skip => {
+                            let total = state.record_skipped(kind, name);
+                            let reason = if queued.is_some() { " and another is queued" } else { "" };
+                            warn!(
+                                "Previous {} of '{}' is still running{}, skipped this one ({} skipped so far)",
+                                describe(kind), name, reason, total
+                            );
                         }
                     }
-                    driver => {
-                        error!("Unsupported database driver for '{}': {}", backup.name, driver);
+                }
+                Some(_) = runs.join_next() => {
+                    if runs.is_empty() && !*stop.borrow() {
+                        if let Some(due) = queued.take() {
+                            info!("Starting queued {}: {}", describe(kind), name);
+                            Self::start_run(name, &mut runs, run(due), &slots, &stop);
+                        }
                     }
                 }
-            } else {
-                error!("Could not calculate next run time for '{}'", backup.name);
-                break Ok(());
             }
         }
+
+        // Let in-flight runs finish; on shutdown the scheduler aborts them after the grace period
+        tokio::select! {
+            _ = async { while runs.join_next().await.is_some() {} } => {}
+            _ = async { let _ = abort.wait_for(|abort| *abort).await; } => {
+                runs.abort_all();
+                // Each aborted run is dropped, killing its dump, before join_next returns it
+                while runs.join_next().await.is_some() {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Spawn one run; it waits for its concurrency slots unless the task is stopped first
+    fn start_run(
+        name: &str,
+        runs: &mut JoinSet<()>,
+        run: RunFuture,
+        slots: &Slots,
+        stop: &watch::Receiver<bool>,
+    ) {
+        let name = name.to_string();
+        let slots = slots.clone();
+        let mut stop = stop.clone();
+        runs.spawn(async move {
+            let _permits = tokio::select! {
+                permits = slots.acquire(&name) => permits,
+                _ = stop.changed() => return,
+            };
+            run.await;
+        });
+    }
+
+    async fn run_backup(
+        config: std::sync::Arc<Config>,
+        backup: std::sync::Arc<BackupConfig>,
+        state: std::sync::Arc<StateStore>,
+        scheduled_at: DateTime<Utc>,
+    ) {
+        // Another instance may already be running this job or this run
+        let lock = match lock::acquire(&config, &backup, Some(scheduled_at)).await {
+            Ok(LockOutcome::Acquired(lock)) => lock,
+            Ok(LockOutcome::Busy(reason)) => {
+                return info!("Skipping scheduled backup '{}': {}", backup.name, reason);
+            }
+            Err(e) => {
+                return error!("✗ Scheduled backup '{}' could not be locked: {:#}", backup.name, e);
+            }
+        };
+
+        info!("Starting scheduled backup: {}", backup.name);
+        let started_at = Utc::now();
+        state.record_attempt(RunKind::Backup, &backup.name, started_at);
+
+        // Execute backup
+        let result = runner::run_backup_job(&config, &backup).await;
+        match &result {
+            Ok(completed) => {
+                state.record_success(RunKind::Backup, &backup.name, started_at);
+                info!("✓ Scheduled backup '{}' completed: {}", backup.name, completed.location);
+            }
+            Err(e) => {
+                error!("✗ Scheduled backup '{}' failed: {:#}", backup.name, e);
+            }
+        }
+        let entry = HistoryEntry::new(&backup.name, Trigger::Scheduled, started_at, &result);
+        if let Err(e) = history::record(&config, &entry) {
+            error!("Failed to record backup history for '{}': {}", backup.name, e);
+        }
+        lock.release().await;
+    }
+
+    /// Verifications share the backup slots: a restore is at least as heavy as a dump
+    async fn run_verification(
+        config: std::sync::Arc<Config>,
+        backup: std::sync::Arc<BackupConfig>,
+        state: std::sync::Arc<StateStore>,
+    ) {
+        info!("Starting scheduled verification: {}", backup.name);
+        let started_at = Utc::now();
+        state.record_attempt(RunKind::Verification, &backup.name, started_at);
+
+        match verify::verify_backup(&config, &backup, None).await {
+            Ok(report) => {
+                if let Err(e) = verify::record(&config, &report) {
+                    error!("Failed to record verification result for '{}': {}", backup.name, e);
+                }
+                if report.success {
+                    state.record_success(RunKind::Verification, &backup.name, started_at);
+                    info!("✓ Scheduled verification of '{}' passed: {}", backup.name, report.archive);
+                } else {
+                    error!(
+                        "✗ Scheduled verification of '{}' failed: {}",
+                        backup.name,
+                        report.error.as_deref().unwrap_or("unknown error")
+                    );
+                }
+            }
+            Err(e) => {
+                error!("✗ Scheduled verification of '{}' could not start: {}", backup.name, e);
+            }
+        }
+    }
+
+    /// Decide from the persisted state whether a task should run right away, and
+    /// if so, which scheduled time the catch-up run claims
+    fn check_catch_up(
+        name: &str,
+        kind: RunKind,
+        schedule_config: &ScheduleConfig,
+        schedule: &Schedule,
+        state: &StateStore,
+    ) -> Result<Option<DateTime<Local>>> {
+        let now = Local::now();
+        let run = state.get(kind, name);
+        let Some(reason) = catch_up_reason(&schedule_config.catch_up, schedule, &run, now)? else {
+            return Ok(None);
+        };
+        info!("Catching up {} '{}' now: {}", describe(kind), name, reason);
+        Ok(Some(catch_up_due(schedule, &run, now)))
+    }
+
+    /// Sleep until the next run is due: immediately once when catching up, otherwise
+    /// at the next cron time. Returns the time the run was due, or `None` if the
+    /// task was stopped first.
+    async fn wait_for_run(
+        name: &str,
+        what: &str,
+        schedule: &Schedule,
+        catch_up: &mut Option<DateTime<Local>>,
+        stop: &mut watch::Receiver<bool>,
+    ) -> Result<Option<DateTime<Local>>> {
+        if *stop.borrow() {
+            return Ok(None);
+        }
+        if let Some(due) = catch_up.take() {
+            return Ok(Some(due));
+        }
+
+        // The cron schedule iterator gives us upcoming times
+        let now = Local::now();
+        let next_run = schedule.after(&now).next()
+            .context(format!("Could not calculate next {} time for '{}'", what, name))?;
+        let sleep_duration = (next_run - now).to_std()?;
+
+        info!(
+            "Next {} for '{}': {} (in {:.0}s)",
+            what,
+            name,
+            next_run.format("%Y-%m-%d %H:%M:%S"),
+            sleep_duration.as_secs_f64()
+        );
+
+        // Sleep until next run, unless the job is unscheduled first
+        tokio::select! {
+            _ = tokio::time::sleep(sleep_duration) => Ok(Some(next_run)),
+            _ = stop.changed() => Ok(None),
+        }
+    }
+}
+
+/// Concurrency slots a run needs: one of its group's and one of the global `--concurrency`
+#[derive(Clone)]
+struct Slots {
+    group: String,
+    group_slots: std::sync::Arc<Semaphore>,
+    global: std::sync::Arc<Semaphore>,
+}
+
+impl Slots {
+    /// Take the group slot first, so a run waiting on a busy server never holds a
+    /// global slot that a run against an idle server could use
+    async fn acquire(&self, name: &str) -> Option<(OwnedSemaphorePermit, OwnedSemaphorePermit)> {
+        if self.group_slots.available_permits() == 0 {
+            info!("'{}' is waiting for a free slot in concurrency group '{}'", name, self.group);
+        }
+        let group = self.group_slots.clone().acquire_owned().await.ok()?;
+        let global = self.global.clone().acquire_owned().await.ok()?;
+        Some((group, global))
+    }
+}
+
+type RunFuture = std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>>;
+
+/// What to do with a scheduled time
+#[derive(Debug, PartialEq, Eq)]
+enum OverlapAction {
+    Start,
+    Queue,
+    Skip,
+}
+
+/// Apply the overlap policy given the number of runs in flight and whether one is already queued
+fn overlap_action(policy: Overlap, running: usize, queued: bool) -> OverlapAction {
+    match policy {
+        _ if running == 0 => OverlapAction::Start,
+        Overlap::Allow => OverlapAction::Start,
+        Overlap::Queue if !queued => OverlapAction::Queue,
+        // A queued run already covers this time
+        Overlap::Queue | Overlap::Skip => OverlapAction::Skip,
+    }
+}
+
+/// SIGTERM or Ctrl+C (SIGINT)
+struct ShutdownSignal {
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+}
+
+impl ShutdownSignal {
+    fn new() -> Result<Self> {
+        Ok(Self {
+            #[cfg(unix)]
+            terminate: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+                .context("Failed to install SIGTERM handler")?,
+        })
+    }
+
+    /// Wait for the next shutdown signal and return its name
+    async fn recv(&mut self) -> &'static str {
+        #[cfg(unix)]
+        let terminate = self.terminate.recv();
+        #[cfg(not(unix))]
+        let terminate = std::future::pending::<Option<()>>();
+
+        tokio::select! {
+            _ = terminate => "SIGTERM",
+            _ = tokio::signal::ctrl_c() => "SIGINT",
+        }
+    }
+}
+
+pub fn describe(kind: RunKind) -> &'static str {
+    match kind {
+        RunKind::Backup => "backup",
+        RunKind::Verification => "verification",
+    }
+}
+
+/// Next time a cron expression fires after `after`
+pub fn next_run(cron: &str, after: DateTime<Local>) -> Result<Option<DateTime<Local>>> {
+    Ok(parse_cron(cron)?.after(&after).next())
+}
+
+/// Parse a cron expression, pointing out the leading seconds field that a
+/// standard 5-field crontab expression lacks
+pub fn parse_cron(cron: &str) -> Result<Schedule> {
+    Schedule::from_str(cron).map_err(|e| match cron.split_whitespace().count() {
+        5 => anyhow::anyhow!(
+            "Invalid cron expression '{}': dbackup expressions start with a seconds field \
+             (sec min hour day month weekday [year]); did you mean '0 {}'?",
+            cron,
+            cron.trim()
+        ),
+        6 | 7 => anyhow::anyhow!("Invalid cron expression '{}': {}", cron, e),
+        fields => anyhow::anyhow!(
+            "Invalid cron expression '{}': expected 6 or 7 fields (sec min hour day month weekday [year]), found {}",
+            cron,
+            fields
+        ),
+    })
+}
+
+/// Upcoming runs of one scheduled task, as previewed by `dbackup schedule`
+#[derive(Debug, Serialize)]
+pub struct UpcomingRuns {
+    pub name: String,
+    pub kind: RunKind,
+    pub cron: String,
+    pub group: String,
+    /// Next fire times; empty when the expression does not parse
+    pub times: Vec<DateTime<Local>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The next `count` fire times after `after` of every scheduled task, sorted by
+/// name. An invalid expression is reported on its task instead of failing the rest.
+pub fn upcoming_runs(config: &Config, count: usize, after: DateTime<Local>) -> Result<Vec<UpcomingRuns>> {
+    let mut upcoming: Vec<_> = scheduled_tasks(config)?
+        .into_iter()
+        .map(|((name, kind), spec)| {
+            let (times, error) = match parse_cron(&spec.schedule.cron) {
+                Ok(schedule) => (schedule.after(&after).take(count).collect(), None),
+                Err(e) => (Vec::new(), Some(format!("{:#}", e))),
+            };
+            UpcomingRuns { name, kind, cron: spec.schedule.cron, group: spec.group, times, error }
+        })
+        .collect();
+    upcoming.sort_by(|a, b| (&a.name, a.kind).cmp(&(&b.name, b.kind)));
+    Ok(upcoming)
+}
+
+/// One run on the `dbackup schedule` timeline
+#[derive(Debug, Serialize)]
+pub struct TimelineRun {
+    pub name: String,
+    pub kind: RunKind,
+    pub group: String,
+    pub start: DateTime<Local>,
+    /// How long the run is expected to take, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_secs: Option<f64>,
+    /// Earlier runs expected to still be going when this one starts
+    pub overlaps: Vec<OverlappingRun>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OverlappingRun {
+    pub name: String,
+    pub kind: RunKind,
+    pub start: DateTime<Local>,
+    /// Both runs compete for the slots of the same concurrency group
+    pub same_group: bool,
+}
+
+/// Every run of the valid `tasks` between `from` and `until` in start order, each
+/// with the earlier runs it overlaps. `estimate` gives a task's expected duration
+/// in seconds; a task without one only overlaps runs starting at the same time.
+pub fn timeline(
+    tasks: &[UpcomingRuns],
+    from: DateTime<Local>,
+    until: DateTime<Local>,
+    estimate: impl Fn(&str, RunKind) -> Option<f64>,
+) -> Vec<TimelineRun> {
+    let mut runs = Vec::new();
+    for task in tasks.iter().filter(|task| task.error.is_none()) {
+        let Ok(schedule) = parse_cron(&task.cron) else { continue };
+        let estimated_secs = estimate(&task.name, task.kind);
+        for start in schedule.after(&from).take_while(|start| *start <= until) {
+            runs.push(TimelineRun {
+                name: task.name.clone(),
+                kind: task.kind,
+                group: task.group.clone(),
+                start,
+                estimated_secs,
+                overlaps: Vec::new(),
+            });
+        }
+    }
+    runs.sort_by(|a, b| (a.start, &a.name, a.kind).cmp(&(b.start, &b.name, b.kind)));
+
+    for i in 0..runs.len() {
+        let overlaps = runs[..i]
+            .iter()
+            .filter(|earlier| {
+                let end = earlier.start + chrono::Duration::milliseconds((earlier.estimated_secs.unwrap_or(0.0) * 1000.0) as i64);
+                earlier.start == runs[i].start || end > runs[i].start
+            })
+            .map(|earlier| OverlappingRun {
+                name: earlier.name.clone(),
+                kind: earlier.kind,
+                start: earlier.start,
+                same_group: earlier.group == runs[i].group,
+            })
+            .collect();
+        runs[i].overlaps = overlaps;
+    }
+    runs
+}
+
+/// Why a task should run immediately under its catch-up policy, if it should
+fn catch_up_reason(
+    policy: &CatchUp,
+    schedule: &Schedule,
+    run: &RunState,
+    now: DateTime<Local>,
+) -> Result<Option<String>> {
+    match policy {
+        CatchUp::Skip => Ok(None),
+        // Without a recorded attempt there is nothing to compare against
+        CatchUp::Missed => Ok(run.last_attempt.and_then(|last| {
+            let missed = schedule.after(&last.with_timezone(&Local)).next()?;
+            (missed <= now).then(|| format!("missed run at {}", missed.format("%Y-%m-%d %H:%M:%S")))
+        })),
+        CatchUp::OlderThan(limit) => {
+            let max_age = retention::parse_duration(limit)?;
+            match run.last_success {
+                None => Ok(Some("no successful run recorded".to_string())),
+                Some(last) => {
+                    let age = (now.with_timezone(&Utc) - last).to_std().unwrap_or_default();
+                    Ok((age > max_age).then(|| {
+                        format!(
+                            "last success at {} is older than {}",
+                            last.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+                            limit
+                        )
+                    }))
+                }
+            }
+        }
+    }
+}
+
+/// Scheduled time a catch-up run stands in for: the latest cron time that has
+/// passed, so instances catching up the same job claim the same run. If this
+/// instance already attempted that run, the catch-up claims the current time.
+fn catch_up_due(schedule: &Schedule, run: &RunState, now: DateTime<Local>) -> DateTime<Local> {
+    schedule
+        .after(&now)
+        .next_back()
+        .filter(|due| run.last_attempt.is_none_or(|last| last < due.with_timezone(&Utc)))
+        .unwrap_or(now)
+}
+
+fn modified_time(path: &std::path::Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Reject a configuration the scheduler could not run, before any task is touched
+fn check_config(config: &Config) -> Result<()> {
+    for backup in &config.backups {
+        driver::lookup(&backup.driver)
+            .ok_or_else(|| anyhow::anyhow!("Backup '{}': unsupported database driver: {}", backup.name, backup.driver))?;
+        let storage_config = config.get_storage_for_backup(backup)
+            .context(format!("Failed to resolve storage for backup '{}'", backup.name))?;
+        RetentionRules::for_backup(backup, &storage_config)
+            .context(format!("Invalid retention policy for backup '{}'", backup.name))?;
+        backup.timeout()?;
+        runner::RetryPolicy::for_backup(backup)?;
     }
+    lock::lease_ttl(config)?;
+
+    if let Some(concurrency) = config.settings.as_ref().and_then(|s| s.concurrency.as_ref()) {
+        if concurrency.per_group == 0 {
+            anyhow::bail!("settings.concurrency.per_group must be at least 1");
+        }
+        if let Some((group, _)) = concurrency.groups.iter().find(|(_, limit)| **limit == 0) {
+            anyhow::bail!("Concurrency limit for group '{}' must be at least 1", group);
+        }
+    }
+
+    for (key, spec) in scheduled_tasks(config)? {
+        let schedule = &spec.schedule;
+        parse_cron(&schedule.cron).context(format!("Invalid schedule for {} '{}'", describe(key.1), key.0))?;
+        if let CatchUp::OlderThan(max_age) = &schedule.catch_up {
+            retention::parse_duration(max_age)
+                .context(format!("Invalid catch_up for {} '{}'", describe(key.1), key.0))?;
+        }
+    }
+    Ok(())
+}
+
+/// Every backup and verification schedule in the config, keyed by job name and kind
+fn scheduled_tasks(config: &Config) -> Result<HashMap<TaskKey, TaskSpec>> {
+    let mut tasks = HashMap::new();
+    for backup in &config.backups {
+        let schedules = [
+            (RunKind::Backup, backup.schedule.as_ref()),
+            (RunKind::Verification, backup.verify.as_ref().and_then(|v| v.schedule.as_ref())),
+        ];
+        for (kind, schedule) in schedules {
+            let Some(schedule) = schedule else { continue };
+            let server = match kind {
+                RunKind::Backup => backup.connection.clone(),
+                RunKind::Verification => verify::verification_server(config, backup)?,
+            };
+            let spec = TaskSpec {
+                backup: backup.clone(),
+                schedule: schedule.clone(),
+                group: concurrency_group(backup, &server),
+                fingerprint: fingerprint(config, backup)?,
+            };
+            if tasks.insert((backup.name.clone(), kind), spec).is_some() {
+                anyhow::bail!("Backup name '{}' is used more than once", backup.name);
+            }
+        }
+    }
+    Ok(tasks)
+}
+
+/// A job's runs are grouped by its `group:` label, or else by the server they connect to
+fn concurrency_group(backup: &BackupConfig, server: &ConnectionConfig) -> String {
+    backup.group.clone().unwrap_or_else(|| format!("{}:{}", server.host, server.port))
+}
+
+/// Serialized form of everything a job reads from the config: its own block, its
+/// resolved storage and the global settings other than the storage catalogue
+fn fingerprint(config: &Config, backup: &BackupConfig) -> Result<String> {
+    let storage = config.get_storage_for_backup(backup)?;
+    let settings = config.settings.clone().map(|mut settings| {
+        settings.storages = None;
+        settings
+    });
+    serde_json::to_string(&(backup, storage, settings)).context("Failed to serialize backup configuration")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{BackupMode, ConnectionConfig, ScheduleConfig, StorageConfig, StorageSelection, StorageReference};
+    use crate::config::{BackupMode, ConnectionConfig, ScheduleConfig, StorageConfig, StorageSelection};
     use std::path::PathBuf;
 
     fn create_test_backup_with_schedule() -> BackupConfig {
@@ -179,6 +917,8 @@ mod tests {
             },
             schedule: Some(ScheduleConfig {
                 cron: "0 2 * * *".to_string(), // Daily at 2 AM
+                catch_up: CatchUp::Skip,
+                overlap: Overlap::Skip,
             }),
             storage: Some(StorageSelection::Inline(StorageConfig {
                 driver: "local".to_string(),
@@ -190,11 +930,16 @@ mod tests {
                 endpoint: None,
                 access_key_id: None,
                 secret_access_key: None,
+                max_total_size: None,
             })),
             mode: BackupMode::Basic,
             parallel_jobs: 2,
             binary_path: None,
+            group: None,
+            timeout: None,
+            retry: None,
             retention: None,
+            verify: None,
         }
     }
 
@@ -210,6 +955,232 @@ mod tests {
         let _ = schedule;
     }
 
+    fn config_from(yaml: &str) -> Config {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    const TWO_JOBS: &str = r#"
+settings:
+  storages:
+    local: {driver: local, path: /tmp/backups}
+backups:
+  - name: app
+    driver: postgresql
+    connection: {host: db1, port: 5432, username: u, password: old, database: app}
+    schedule: {cron: "0 0 2 * * *"}
+    storage: {ref: local}
+  - name: shop
+    driver: mysql
+    connection: {host: db2, port: 3306, username: u, password: p, database: shop}
+    schedule: {cron: "0 0 3 * * *"}
+    storage: {ref: local}
+    verify: {server: {username: verifier}, schedule: {cron: "0 0 5 * * SUN"}}
+"#;
+
+    #[test]
+    fn test_reload_diff_only_changes_edited_jobs() {
+        let old = scheduled_tasks(&config_from(TWO_JOBS)).unwrap();
+        assert_eq!(old.len(), 3);
+
+        let edited = scheduled_tasks(&config_from(&TWO_JOBS.replace("password: old", "password: new"))).unwrap();
+        let app = ("app".to_string(), RunKind::Backup);
+        let shop = ("shop".to_string(), RunKind::Backup);
+        assert_ne!(old[&app].fingerprint, edited[&app].fingerprint);
+        assert_eq!(old[&shop].fingerprint, edited[&shop].fingerprint);
+
+        // Editing a shared storage touches every job that references it
+        let moved = scheduled_tasks(&config_from(&TWO_JOBS.replace("/tmp/backups", "/srv/backups"))).unwrap();
+        assert_ne!(old[&app].fingerprint, moved[&app].fingerprint);
+        assert_ne!(old[&shop].fingerprint, moved[&shop].fingerprint);
+    }
+
+    #[test]
+    fn test_concurrency_groups() {
+        let tasks = scheduled_tasks(&config_from(TWO_JOBS)).unwrap();
+        assert_eq!(tasks[&("app".to_string(), RunKind::Backup)].group, "db1:5432");
+        assert_eq!(tasks[&("shop".to_string(), RunKind::Verification)].group, "db2:3306");
+
+        let yaml = TWO_JOBS
+            .replace("    schedule: {cron: \"0 0 3 * * *\"}", "    schedule: {cron: \"0 0 3 * * *\"}\n    group: reporting")
+            .replace("settings:", "settings:\n  concurrency: {per_group: 2, groups: {reporting: 3}}");
+        let config = config_from(&yaml);
+        let tasks = scheduled_tasks(&config).unwrap();
+        assert_eq!(tasks[&("shop".to_string(), RunKind::Backup)].group, "reporting");
+        assert_eq!(config.group_limit("reporting"), 3);
+        assert_eq!(config.group_limit("db1:5432"), 2);
+        assert_eq!(config_from(TWO_JOBS).group_limit("db1:5432"), 1);
+
+        assert!(check_config(&config_from(&yaml.replace("reporting: 3", "reporting: 0"))).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_slots_limit_each_group() {
+        let global = std::sync::Arc::new(Semaphore::new(2));
+        let slots = |group: &str, group_slots: &std::sync::Arc<Semaphore>| Slots {
+            group: group.to_string(),
+            group_slots: group_slots.clone(),
+            global: global.clone(),
+        };
+        let db1 = std::sync::Arc::new(Semaphore::new(1));
+        let db2 = std::sync::Arc::new(Semaphore::new(1));
+
+        let first = slots("db1", &db1).acquire("job").await.unwrap();
+        // A second run against db1 waits without taking a global slot...
+        let blocked = tokio::time::timeout(Duration::from_millis(50), slots("db1", &db1).acquire("job")).await;
+        assert!(blocked.is_err());
+        // ...so a run against db2 can start
+        assert!(slots("db2", &db2).acquire("job").await.is_some());
+
+        drop(first);
+        assert!(slots("db1", &db1).acquire("job").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_group_limit_change_keeps_running_slots() {
+        let yaml = TWO_JOBS.replace("settings:", "settings:\n  concurrency: {per_group: 2}");
+        let mut scheduler = BackupScheduler::new(PathBuf::from("backup.yml"), config_from(&yaml), 4, false, Duration::ZERO);
+        let first = scheduler.slots("db1:5432").acquire("job").await.unwrap();
+        let second = scheduler.slots("db1:5432").acquire("job").await.unwrap();
+
+        // Shrinking to one slot: the new limit is reached until both runs finish
+        scheduler.config = config_from(&yaml.replace("per_group: 2", "per_group: 1"));
+        let group_slots = scheduler.slots("db1:5432").group_slots;
+        drop(first);
+        tokio::task::yield_now().await;
+        assert_eq!(group_slots.available_permits(), 0);
+        drop(second);
+        assert_eq!(group_slots.available_permits(), 1);
+
+        // Growing adds slots to the same semaphore
+        scheduler.config = config_from(&yaml.replace("per_group: 2", "per_group: 3"));
+        assert!(std::sync::Arc::ptr_eq(&scheduler.slots("db1:5432").group_slots, &group_slots));
+        assert_eq!(group_slots.available_permits(), 3);
+    }
+
+    #[test]
+    fn test_reload_follows_state_dir() {
+        let dir = std::env::temp_dir().join(format!("dbackup_test_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("backup.yml");
+        let with_state_dir = |name: &str| format!("settings: {{state_dir: {}}}\nbackups: []", dir.join(name).display());
+
+        std::fs::write(&config_path, with_state_dir("old")).unwrap();
+        let config = Config::from_file(&config_path).unwrap();
+        let mut scheduler = BackupScheduler::new(config_path.clone(), config, 1, false, Duration::ZERO);
+
+        std::fs::write(&config_path, with_state_dir("new")).unwrap();
+        scheduler.reload().unwrap();
+        scheduler.state.record_attempt(RunKind::Backup, "app", Utc::now());
+        assert!(dir.join("new").join("scheduler.json").is_file());
+        assert!(!dir.join("old").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_check_config_rejects_invalid_reload() {
+        assert!(check_config(&config_from(TWO_JOBS)).is_ok());
+        assert!(check_config(&config_from(&TWO_JOBS.replace("0 0 3 * * *", "every night"))).is_err());
+        assert!(check_config(&config_from(&TWO_JOBS.replace("driver: mysql", "driver: oracle"))).is_err());
+        assert!(check_config(&config_from(&TWO_JOBS.replace("{ref: local}", "{ref: missing}"))).is_err());
+    }
+
+    #[test]
+    fn test_catch_up_reason() {
+        use chrono::TimeZone;
+
+        let schedule = Schedule::from_str("0 0 2 * * *").unwrap();
+        let now = Local.with_ymd_and_hms(2026, 2, 18, 9, 0, 0).unwrap();
+        let at = |day, hour| Some(Local.with_ymd_and_hms(2026, 2, day, hour, 0, 0).unwrap().with_timezone(&Utc));
+
+        // Down over the 02:00 run on the 18th
+        let missed = RunState { last_attempt: at(17, 2), last_success: at(17, 2), ..Default::default() };
+        assert!(catch_up_reason(&CatchUp::Missed, &schedule, &missed, now).unwrap().is_some());
+        assert!(catch_up_reason(&CatchUp::Skip, &schedule, &missed, now).unwrap().is_none());
+
+        // Ran at 02:00 today, nothing missed; a fresh install has nothing to catch up
+        let current = RunState { last_attempt: at(18, 2), last_success: at(17, 2), ..Default::default() };
+        assert!(catch_up_reason(&CatchUp::Missed, &schedule, &current, now).unwrap().is_none());
+        assert!(catch_up_reason(&CatchUp::Missed, &schedule, &RunState::default(), now).unwrap().is_none());
+
+        // Today's run failed: the last success is 31h old
+        let older_than = |limit: &str| CatchUp::OlderThan(limit.to_string());
+        assert!(catch_up_reason(&older_than("36h"), &schedule, &current, now).unwrap().is_none());
+        assert!(catch_up_reason(&older_than("1d"), &schedule, &current, now).unwrap().is_some());
+        assert!(catch_up_reason(&older_than("36h"), &schedule, &RunState::default(), now).unwrap().is_some());
+
+        // A catch-up claims the run it stands in for, unless that run was already attempted here
+        let today = Local.with_ymd_and_hms(2026, 2, 18, 2, 0, 0).unwrap();
+        assert_eq!(catch_up_due(&schedule, &missed, now), today);
+        assert_eq!(catch_up_due(&schedule, &RunState::default(), now), today);
+        assert_eq!(catch_up_due(&schedule, &current, now), now);
+    }
+
+    #[test]
+    fn test_catch_up_config() {
+        let parse = |yaml: &str| serde_yaml::from_str::<ScheduleConfig>(yaml).unwrap().catch_up;
+        assert_eq!(parse("cron: '0 0 2 * * *'"), CatchUp::Skip);
+        assert_eq!(parse("{cron: '0 0 2 * * *', catch_up: missed}"), CatchUp::Missed);
+        assert_eq!(parse("{cron: '0 0 2 * * *', catch_up: 36h}"), CatchUp::OlderThan("36h".to_string()));
+
+        let invalid = TWO_JOBS.replace(r#"schedule: {cron: "0 0 2 * * *"}"#, r#"schedule: {cron: "0 0 2 * * *", catch_up: soon}"#);
+        assert!(check_config(&config_from(&invalid)).is_err());
+    }
+
+    #[test]
+    fn test_overlap_action() {
+        let parse = |yaml: &str| serde_yaml::from_str::<ScheduleConfig>(yaml).unwrap().overlap;
+        assert_eq!(parse("cron: '0 0 * * * *'"), Overlap::Skip);
+        assert_eq!(parse("{cron: '0 0 * * * *', overlap: queue}"), Overlap::Queue);
+        assert!(serde_yaml::from_str::<ScheduleConfig>("{cron: '0 0 * * * *', overlap: later}").is_err());
+
+        for policy in [Overlap::Skip, Overlap::Queue, Overlap::Allow] {
+            assert_eq!(overlap_action(policy, 0, false), OverlapAction::Start);
+        }
+        assert_eq!(overlap_action(Overlap::Skip, 1, false), OverlapAction::Skip);
+        assert_eq!(overlap_action(Overlap::Queue, 1, false), OverlapAction::Queue);
+        // At most one run waits behind the current one
+        assert_eq!(overlap_action(Overlap::Queue, 1, true), OverlapAction::Skip);
+        assert_eq!(overlap_action(Overlap::Allow, 2, false), OverlapAction::Start);
+    }
+
+    #[test]
+    fn test_parse_cron_suggests_seconds_field() {
+        let err = parse_cron("0 2 * * *").unwrap_err().to_string();
+        assert!(err.contains("did you mean '0 0 2 * * *'"), "{}", err);
+        let err = parse_cron("0 2 * *").unwrap_err().to_string();
+        assert!(err.contains("found 4"), "{}", err);
+        assert!(parse_cron("0 0 2 * * *").is_ok());
+    }
+
+    #[test]
+    fn test_upcoming_runs_and_timeline() {
+        use chrono::TimeZone;
+
+        let config = config_from(&TWO_JOBS.replace("0 0 5 * * SUN", "0 5 * * SUN"));
+        let now = Local.with_ymd_and_hms(2026, 2, 18, 0, 0, 0).unwrap();
+        let upcoming = upcoming_runs(&config, 2, now).unwrap();
+        let summary: Vec<_> = upcoming.iter().map(|u| (u.name.as_str(), u.kind, u.times.len(), u.error.is_some())).collect();
+        assert_eq!(
+            summary,
+            [
+                ("app", RunKind::Backup, 2, false),
+                ("shop", RunKind::Backup, 2, false),
+                ("shop", RunKind::Verification, 0, true),
+            ]
+        );
+        assert_eq!(upcoming[0].times[0], Local.with_ymd_and_hms(2026, 2, 18, 2, 0, 0).unwrap());
+
+        // app takes 90 minutes, so the 03:00 shop run overlaps it; shop's own length is unknown
+        let runs = timeline(&upcoming, now, now + chrono::Duration::hours(24), |name, _| (name == "app").then_some(5400.0));
+        let overlaps: Vec<_> = runs
+            .iter()
+            .map(|run| (run.name.as_str(), run.overlaps.iter().map(|o| o.name.as_str()).collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(overlaps, [("app", vec![]), ("shop", vec!["app"])]);
+        assert!(!runs[1].overlaps[0].same_group);
+    }
+
     #[test]
     fn test_invalid_cron_expression() {
         let invalid_cron = "invalid cron";
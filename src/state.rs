@@ -0,0 +1,223 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::warn;
+
+const STATE_FILE: &str = "scheduler.json";
+
+/// Last known runs of one scheduled task
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunState {
+    /// When the most recent run started, whatever its outcome
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_attempt: Option<DateTime<Utc>>,
+    /// When the most recent successful run started
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_success: Option<DateTime<Utc>>,
+    /// Scheduled times dropped because the previous run was still going
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub skipped: u64,
+    /// Scheduled times deferred until the previous run finished
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub queued: u64,
+}
+
+fn is_zero(count: &u64) -> bool {
+    *count == 0
+}
+
+/// Contents of the scheduler state file, keyed by backup name
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SchedulerState {
+    #[serde(default)]
+    backups: BTreeMap<String, RunState>,
+    #[serde(default)]
+    verifications: BTreeMap<String, RunState>,
+}
+
+/// Which kind of scheduled run a state entry describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunKind {
+    Backup,
+    Verification,
+}
+
+/// Scheduler state persisted in `<state_dir>/scheduler.json`.
+///
+/// Survives restarts so missed runs can be caught up. Several processes may
+/// share the file: every change re-reads it under an exclusive lock. Write
+/// failures are logged and never fail a backup.
+pub struct StateStore {
+    path: PathBuf,
+    /// Last state read or written, used while the file cannot be read
+    state: Mutex<SchedulerState>,
+}
+
+impl StateStore {
+    /// Load the state file; a missing or unreadable file starts from empty state
+    pub fn open(state_dir: &Path) -> Self {
+        let store = Self { path: state_dir.join(STATE_FILE), state: Mutex::default() };
+        let mut state = store.state.lock().unwrap_or_else(|e| e.into_inner());
+        store.refresh(&mut state);
+        drop(state);
+        store
+    }
+
+    pub fn get(&self, kind: RunKind, name: &str) -> RunState {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        self.refresh(&mut state);
+        state.runs(kind).get(name).cloned().unwrap_or_default()
+    }
+
+    /// Record that a run started at `at`
+    pub fn record_attempt(&self, kind: RunKind, name: &str, at: DateTime<Utc>) {
+        self.update(kind, name, |run| run.last_attempt = Some(at));
+    }
+
+    /// Record that the run started at `at` succeeded
+    pub fn record_success(&self, kind: RunKind, name: &str, at: DateTime<Utc>) {
+        self.update(kind, name, |run| run.last_success = Some(at));
+    }
+
+    /// Count a scheduled time skipped because of an overlap, returning the total
+    pub fn record_skipped(&self, kind: RunKind, name: &str) -> u64 {
+        self.update(kind, name, |run| {
+            run.skipped += 1;
+            run.skipped
+        })
+    }
+
+    /// Count a scheduled time queued behind a running run, returning the total
+    pub fn record_queued(&self, kind: RunKind, name: &str) -> u64 {
+        self.update(kind, name, |run| {
+            run.queued += 1;
+            run.queued
+        })
+    }
+
+    /// Apply `change` to the latest state on disk and write it back, so changes
+    /// made by other processes since this one last read the file are kept
+    fn update<T>(&self, kind: RunKind, name: &str, change: impl FnOnce(&mut RunState) -> T) -> T {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let lock = self.lock_file();
+        self.refresh(&mut state);
+        let result = change(state.runs_mut(kind).entry(name.to_string()).or_default());
+        // The lock is held until the new file is in place
+        let saved = match lock {
+            Ok(_lock) => self.save(&state),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = saved {
+            warn!("Failed to save scheduler state: {:#}", e);
+        }
+        result
+    }
+
+    /// Replace `state` with the file's contents, if it can be read
+    fn refresh(&self, state: &mut SchedulerState) {
+        match std::fs::read_to_string(&self.path) {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(current) => *state = current,
+                Err(e) => warn!("Ignoring invalid scheduler state {}: {}", self.path.display(), e),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Could not read scheduler state {}: {}", self.path.display(), e),
+        }
+    }
+
+    /// Exclusive lock on `scheduler.json.lock`, released when the file is closed.
+    /// The state file itself is replaced on every write, so it cannot carry the lock.
+    fn lock_file(&self) -> Result<File> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)
+                .context(format!("Failed to create state directory: {}", dir.display()))?;
+        }
+        let path = self.path.with_extension("json.lock");
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .context(format!("Failed to open {}", path.display()))?;
+        file.lock().context(format!("Failed to lock {}", path.display()))?;
+        Ok(file)
+    }
+
+    /// Write through a temporary file so a crash never leaves a truncated state file
+    fn save(&self, state: &SchedulerState) -> Result<()> {
+        let json = serde_json::to_string_pretty(state).context("Failed to serialize scheduler state")?;
+        let tmp = self.path.with_extension(format!("json.{}.tmp", std::process::id()));
+        std::fs::write(&tmp, json).context(format!("Failed to write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path).context(format!("Failed to replace {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+impl SchedulerState {
+    fn runs(&self, kind: RunKind) -> &BTreeMap<String, RunState> {
+        match kind {
+            RunKind::Backup => &self.backups,
+            RunKind::Verification => &self.verifications,
+        }
+    }
+
+    fn runs_mut(&mut self, kind: RunKind) -> &mut BTreeMap<String, RunState> {
+        match kind {
+            RunKind::Backup => &mut self.backups,
+            RunKind::Verification => &mut self.verifications,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_state_round_trip() {
+        let dir = std::env::temp_dir().join(format!("dbackup_test_{}", uuid::Uuid::new_v4()));
+        let attempt = Utc.with_ymd_and_hms(2026, 2, 18, 2, 0, 0).unwrap();
+        let success = Utc.with_ymd_and_hms(2026, 2, 17, 2, 0, 0).unwrap();
+
+        let store = StateStore::open(&dir);
+        assert_eq!(store.get(RunKind::Backup, "app"), RunState::default());
+        store.record_success(RunKind::Backup, "app", success);
+        store.record_attempt(RunKind::Backup, "app", attempt);
+        store.record_skipped(RunKind::Backup, "app");
+        assert_eq!(store.record_skipped(RunKind::Backup, "app"), 2);
+
+        let reopened = StateStore::open(&dir);
+        let run = reopened.get(RunKind::Backup, "app");
+        assert_eq!(run.last_attempt, Some(attempt));
+        assert_eq!(run.last_success, Some(success));
+        assert_eq!((run.skipped, run.queued), (2, 0));
+        assert_eq!(reopened.get(RunKind::Verification, "app"), RunState::default());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_stores_sharing_a_file() {
+        let dir = std::env::temp_dir().join(format!("dbackup_test_{}", uuid::Uuid::new_v4()));
+        let at = Utc.with_ymd_and_hms(2026, 2, 18, 2, 0, 0).unwrap();
+
+        // Two daemons on one host, both started before either wrote anything
+        let first = StateStore::open(&dir);
+        let second = StateStore::open(&dir);
+        first.record_success(RunKind::Backup, "app", at);
+        second.record_attempt(RunKind::Backup, "shop", at);
+        second.record_attempt(RunKind::Backup, "app", at);
+
+        assert_eq!(first.get(RunKind::Backup, "shop").last_attempt, Some(at));
+        let run = StateStore::open(&dir).get(RunKind::Backup, "app");
+        assert_eq!((run.last_attempt, run.last_success), (Some(at), Some(at)));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,16 +1,78 @@
 use crate::config::StorageConfig;
 use anyhow::{Context, Result};
-use std::path::PathBuf;
-use tracing::{info, debug};
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncRead;
+use tracing::{debug, info};
 use aws_sdk_s3::primitives::ByteStream;
 
+/// Number of body chunks buffered between an S3 download and its reader
+const STREAM_CHANNEL_CAPACITY: usize = 8;
+
+/// Metadata about an object in a storage backend
+#[derive(Debug, Clone)]
+pub struct ObjectInfo {
+    /// Key relative to the storage root (local directory or S3 prefix)
+    pub key: String,
+    pub size: u64,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+/// Streaming reader over an object's contents
+pub type ObjectReader = Box<dyn AsyncRead + Send + Unpin>;
+
+/// Object-store abstraction implemented by every storage driver.
+///
+/// Keys are relative to the storage root: the configured directory for local
+/// storage, the configured prefix for S3.
 #[async_trait::async_trait]
 pub trait StorageBackend: Send + Sync {
     /// Store backup data from file to the configured storage backend
-    async fn store(&self, local_path: &PathBuf, filename: &str) -> Result<String>;
+    async fn store(&self, local_path: &Path, filename: &str) -> Result<String>;
+
+    /// List objects whose key starts with `prefix`
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>>;
+
+    /// Open an object for reading
+    async fn get(&self, key: &str) -> Result<ObjectReader>;
+
+    /// Delete an object
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// Metadata for an object, or `None` if it does not exist
+    async fn stat(&self, key: &str) -> Result<Option<ObjectInfo>>;
+
+    /// Whether an object exists
+    async fn exists(&self, key: &str) -> Result<bool> {
+        Ok(self.stat(key).await?.is_some())
+    }
+
+    /// Resolve a user-supplied reference (file name, path, key or URL) to a key
+    fn resolve_key(&self, reference: &str) -> String;
+
+    /// Full display location of an object, e.g. a path or `s3://` URL
+    fn location_of(&self, key: &str) -> String;
+
+    /// Path of the object on the local filesystem, if the backend is local
+    fn local_path(&self, _key: &str) -> Option<PathBuf> {
+        None
+    }
     
     /// Get the display name for where the backup was stored
     fn get_location_display(&self) -> String;
+
+    /// Read a small object together with its version tag (ETag), or `None` if it
+    /// does not exist. Only backends with conditional writes support it.
+    async fn read_versioned(&self, _key: &str) -> Result<Option<(Vec<u8>, String)>> {
+        anyhow::bail!("{} does not support versioned objects", self.get_location_display())
+    }
+
+    /// Write a small object only if it still has the version `expected`, or, with
+    /// `None`, only if it does not exist yet. Returns the new version, or `None` if
+    /// another writer got there first.
+    async fn put_if(&self, _key: &str, _body: Vec<u8>, _expected: Option<&str>) -> Result<Option<String>> {
+        anyhow::bail!("{} does not support conditional writes", self.get_location_display())
+    }
 }
 
 /// Local filesystem storage backend
@@ -34,13 +96,96 @@ impl LocalStorage {
 
 #[async_trait::async_trait]
 impl StorageBackend for LocalStorage {
-    async fn store(&self, local_path: &PathBuf, _filename: &str) -> Result<String> {
-        // File is already at the desired location for local storage
-        // This is handled by the backup process directly
-        let location = local_path.display().to_string();
+    async fn store(&self, local_path: &Path, filename: &str) -> Result<String> {
+        // Backups are usually written straight into the directory by the
+        // backup process; only copy files that live elsewhere
+        let destination = self.path.join(filename);
+        if local_path != destination {
+            tokio::fs::copy(local_path, &destination)
+                .await
+                .context(format!("Failed to copy backup to {}", destination.display()))?;
+        }
+        let location = destination.display().to_string();
         info!("Backup file available at: {}", location);
         Ok(location)
     }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>> {
+        if !self.path.exists() {
+            debug!("Backup path does not exist: {}", self.path.display());
+            return Ok(Vec::new());
+        }
+
+        let mut objects = Vec::new();
+        let mut entries = tokio::fs::read_dir(&self.path)
+            .await
+            .context("Failed to read backup directory")?;
+
+        while let Some(entry) = entries.next_entry().await.context("Failed to read directory entry")? {
+            let Ok(key) = entry.file_name().into_string() else { continue };
+            if !key.starts_with(prefix) {
+                continue;
+            }
+
+            let metadata = entry.metadata().await.context("Failed to read file metadata")?;
+            // Skip directories
+            if !metadata.is_file() {
+                continue;
+            }
+
+            objects.push(ObjectInfo {
+                key,
+                size: metadata.len(),
+                last_modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+            });
+        }
+
+        Ok(objects)
+    }
+
+    async fn get(&self, key: &str) -> Result<ObjectReader> {
+        let path = self.path.join(key);
+        let file = tokio::fs::File::open(&path)
+            .await
+            .context(format!("Failed to open {}", path.display()))?;
+        Ok(Box::new(file))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let path = self.path.join(key);
+        tokio::fs::remove_file(&path)
+            .await
+            .context(format!("Failed to delete {}", path.display()))
+    }
+
+    async fn stat(&self, key: &str) -> Result<Option<ObjectInfo>> {
+        let path = self.path.join(key);
+        match tokio::fs::metadata(&path).await {
+            Ok(metadata) if metadata.is_file() => Ok(Some(ObjectInfo {
+                key: key.to_string(),
+                size: metadata.len(),
+                last_modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+            })),
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).context(format!("Failed to stat {}", path.display())),
+        }
+    }
+
+    fn resolve_key(&self, reference: &str) -> String {
+        Path::new(reference)
+            .strip_prefix(&self.path)
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|_| reference.to_string())
+    }
+
+    fn location_of(&self, key: &str) -> String {
+        self.path.join(key).display().to_string()
+    }
+
+    fn local_path(&self, key: &str) -> Option<PathBuf> {
+        Some(self.path.join(key))
+    }
     
     fn get_location_display(&self) -> String {
         self.path.display().to_string()
@@ -64,8 +209,7 @@ impl S3Storage {
             .context("S3 storage requires 'region' configuration")?
             .clone();
         
-        let prefix = config.prefix.as_ref()
-            .map(|p| p.clone())
+        let prefix = config.prefix.clone()
             .unwrap_or_else(|| "backups/".to_string());
         
         info!("Initializing S3 storage: bucket={}, region={}", bucket, region);
@@ -127,24 +271,52 @@ impl S3Storage {
             client,
         })
     }
-    
-    /// Delete old backups based on retention duration
-    pub async fn cleanup_old_backups(&self, retention_duration: &std::time::Duration) -> Result<()> {
-        use std::time::SystemTime;
+
+    fn full_key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+}
+
+#[async_trait::async_trait]
+impl StorageBackend for S3Storage {
+    async fn store(&self, local_path: &Path, filename: &str) -> Result<String> {
+        info!("Uploading backup to S3: s3://{}/{}{}", self.bucket, self.prefix, filename);
+        
+        // Read the backup file
+        let file_data = tokio::fs::read(local_path)
+            .await
+            .context("Failed to read backup file")?;
         
-        info!("Listing objects in bucket: {}, prefix: {}", self.bucket, self.prefix);
+        let key = format!("{}{}", self.prefix, filename);
         
-        let now = SystemTime::now();
-        let cutoff_time = now - *retention_duration;
+        // Upload to S3
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(&key)
+            .body(ByteStream::from(file_data))
+            .send()
+            .await
+            .context(format!("Failed to upload to S3: {}", key))?;
+        
+        info!("Successfully uploaded to S3: s3://{}/{}", self.bucket, key);
         
-        let mut deleted_count = 0;
+        let location = format!("s3://{}/{}", self.bucket, key);
+        Ok(location)
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectInfo>> {
+        let full_prefix = format!("{}{}", self.prefix, prefix);
+        debug!("Listing objects in bucket: {}, prefix: {}", self.bucket, full_prefix);
+
+        let mut objects = Vec::new();
         let mut continuation_token: Option<String> = None;
         
         loop {
             let mut list_request = self.client
                 .list_objects_v2()
                 .bucket(&self.bucket)
-                .prefix(&self.prefix);
+                .prefix(&full_prefix);
             
             if let Some(token) = continuation_token {
                 list_request = list_request.continuation_token(token);
@@ -155,35 +327,14 @@ impl S3Storage {
                 .await
                 .context("Failed to list S3 objects")?;
             
-            // Process files
-            let contents = response.contents();
-            for obj in contents {
-                if let Some(last_modified) = obj.last_modified() {
-                    // Convert AWS DateTime to SystemTime
-                    // AWS DateTime is Unix timestamp, convert via u64
-                    let timestamp_secs = last_modified.secs();
-                    let nanos = last_modified.subsec_nanos();
-                    let obj_time = SystemTime::UNIX_EPOCH + std::time::Duration::new(timestamp_secs as u64, nanos);
-                    
-                    if obj_time < cutoff_time {
-                        if let Some(key) = obj.key() {
-                            match self.client
-                                .delete_object()
-                                .bucket(&self.bucket)
-                                .key(key)
-                                .send()
-                                .await {
-                                Ok(_) => {
-                                    info!("Deleted old S3 object: s3://{}/{}", self.bucket, key);
-                                    deleted_count += 1;
-                                }
-                                Err(e) => {
-                                    debug!("Failed to delete S3 object {}: {}", key, e);
-                                }
-                            }
-                        }
-                    }
-                }
+            for obj in response.contents() {
+                let Some(key) = obj.key().and_then(|k| k.strip_prefix(&self.prefix)) else { continue };
+
+                objects.push(ObjectInfo {
+                    key: key.to_string(),
+                    size: obj.size().unwrap_or(0).max(0) as u64,
+                    last_modified: obj.last_modified().and_then(to_utc),
+                });
             }
             
             // Check if there are more results
@@ -193,43 +344,162 @@ impl S3Storage {
                 break;
             }
         }
-        
-        info!("S3 retention cleanup removed {} object(s)", deleted_count);
-        Ok(())
+
+        Ok(objects)
     }
-}
 
-#[async_trait::async_trait]
-impl StorageBackend for S3Storage {
-    async fn store(&self, local_path: &PathBuf, filename: &str) -> Result<String> {
-        info!("Uploading backup to S3: s3://{}/{}{}", self.bucket, self.prefix, filename);
-        
-        // Read the backup file
-        let file_data = tokio::fs::read(local_path)
+    async fn get(&self, key: &str) -> Result<ObjectReader> {
+        let full_key = self.full_key(key);
+        let response = self.client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(&full_key)
+            .send()
             .await
-            .context("Failed to read backup file")?;
-        
-        let key = format!("{}{}", self.prefix, filename);
-        
-        // Upload to S3
+            .context(format!("Failed to download from S3: {}", full_key))?;
+
+        // Pump the body through a channel so callers get a plain AsyncRead
+        // that reports download errors instead of ending early
+        let (sender, receiver) = tokio::sync::mpsc::channel(STREAM_CHANNEL_CAPACITY);
+        let mut body = response.body;
+        tokio::spawn(async move {
+            loop {
+                let item = match body.try_next().await {
+                    Ok(Some(chunk)) => Ok(chunk.to_vec()),
+                    Ok(None) => break,
+                    Err(e) => Err(std::io::Error::other(format!("Failed to read S3 object {}: {}", full_key, e))),
+                };
+                let failed = item.is_err();
+                if sender.send(item).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        Ok(Box::new(ChannelReader { receiver, chunk: Vec::new(), position: 0 }))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let full_key = self.full_key(key);
         self.client
-            .put_object()
+            .delete_object()
             .bucket(&self.bucket)
-            .key(&key)
-            .body(ByteStream::from(file_data))
+            .key(&full_key)
             .send()
             .await
-            .context(format!("Failed to upload to S3: {}", key))?;
-        
-        info!("Successfully uploaded to S3: s3://{}/{}", self.bucket, key);
-        
-        let location = format!("s3://{}/{}", self.bucket, key);
-        Ok(location)
+            .context(format!("Failed to delete S3 object: {}", full_key))?;
+        Ok(())
+    }
+
+    async fn stat(&self, key: &str) -> Result<Option<ObjectInfo>> {
+        let full_key = self.full_key(key);
+        match self.client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(&full_key)
+            .send()
+            .await {
+            Ok(response) => Ok(Some(ObjectInfo {
+                key: key.to_string(),
+                size: response.content_length().unwrap_or(0).max(0) as u64,
+                last_modified: response.last_modified().and_then(to_utc),
+            })),
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => Ok(None),
+            Err(e) => Err(e).context(format!("Failed to stat S3 object: {}", full_key)),
+        }
+    }
+
+    /// Accepts a full `s3://bucket/key` URL, a key that already includes the
+    /// configured prefix, or a bare filename relative to the prefix.
+    fn resolve_key(&self, reference: &str) -> String {
+        let bucket_url = format!("s3://{}/", self.bucket);
+        let key = reference.strip_prefix(&bucket_url).unwrap_or(reference);
+        key.strip_prefix(&self.prefix).unwrap_or(key).to_string()
+    }
+
+    fn location_of(&self, key: &str) -> String {
+        format!("s3://{}/{}", self.bucket, self.full_key(key))
     }
     
     fn get_location_display(&self) -> String {
         format!("s3://{}/{}", self.bucket, self.prefix)
     }
+
+    async fn read_versioned(&self, key: &str) -> Result<Option<(Vec<u8>, String)>> {
+        let full_key = self.full_key(key);
+        let response = match self.client.get_object().bucket(&self.bucket).key(&full_key).send().await {
+            Ok(response) => response,
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => return Ok(None),
+            Err(e) => return Err(e).context(format!("Failed to read S3 object: {}", full_key)),
+        };
+        let etag = response.e_tag().unwrap_or_default().to_string();
+        let body = response.body
+            .collect()
+            .await
+            .context(format!("Failed to read S3 object: {}", full_key))?;
+        Ok(Some((body.to_vec(), etag)))
+    }
+
+    async fn put_if(&self, key: &str, body: Vec<u8>, expected: Option<&str>) -> Result<Option<String>> {
+        let full_key = self.full_key(key);
+        let request = self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(&full_key)
+            .body(ByteStream::from(body));
+        let request = match expected {
+            Some(etag) => request.if_match(etag),
+            None => request.if_none_match("*"),
+        };
+
+        match request.send().await {
+            Ok(response) => Ok(Some(response.e_tag().unwrap_or_default().to_string())),
+            // 412: the condition failed; 409: a concurrent conditional write won
+            Err(e) if e.raw_response().is_some_and(|r| matches!(r.status().as_u16(), 409 | 412)) => Ok(None),
+            Err(e) => Err(e).context(format!("Failed to write S3 object: {}", full_key)),
+        }
+    }
+}
+
+/// AsyncRead over chunks received from a background download task
+struct ChannelReader {
+    receiver: tokio::sync::mpsc::Receiver<std::io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl AsyncRead for ChannelReader {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        use std::task::Poll;
+
+        while self.position >= self.chunk.len() {
+            match self.receiver.poll_recv(cx) {
+                Poll::Ready(Some(Ok(chunk))) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Err(e)),
+                // Sender finished: end of stream
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        let available = &self.chunk[self.position..];
+        let n = available.len().min(buf.remaining());
+        buf.put_slice(&available[..n]);
+        self.position += n;
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Convert an AWS timestamp to a chrono UTC timestamp
+fn to_utc(time: &aws_sdk_s3::primitives::DateTime) -> Option<DateTime<Utc>> {
+    DateTime::<Utc>::from_timestamp(time.secs(), time.subsec_nanos())
 }
 
 /// Factory function to create the appropriate storage backend
@@ -248,3 +518,56 @@ pub async fn create_storage(config: &StorageConfig) -> Result<Box<dyn StorageBac
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_local_storage_operations() {
+        let root = std::env::temp_dir().join(format!("dbackup_storage_test_{}", uuid::Uuid::new_v4()));
+        let config = StorageConfig {
+            driver: "local".to_string(),
+            path: Some(root.clone()),
+            filename_prefix: None,
+            bucket: None,
+            region: None,
+            prefix: None,
+            endpoint: None,
+            access_key_id: None,
+            secret_access_key: None,
+            max_total_size: None,
+        };
+        let storage = LocalStorage::new(&config).unwrap();
+
+        // Files outside the root are copied in
+        let source = std::env::temp_dir().join(format!("dbackup_storage_src_{}", uuid::Uuid::new_v4()));
+        std::fs::write(&source, b"archive data").unwrap();
+        storage.store(&source, "backup_1.sql.gz").await.unwrap();
+        std::fs::write(root.join("other.txt"), b"x").unwrap();
+        std::fs::create_dir(root.join("backup_dir")).unwrap();
+
+        let listed = storage.list("backup_").await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].key, "backup_1.sql.gz");
+        assert_eq!(listed[0].size, 12);
+        assert!(listed[0].last_modified.is_some());
+
+        let mut contents = Vec::new();
+        storage.get("backup_1.sql.gz").await.unwrap().read_to_end(&mut contents).await.unwrap();
+        assert_eq!(contents, b"archive data");
+
+        assert_eq!(storage.resolve_key(&root.join("backup_1.sql.gz").display().to_string()), "backup_1.sql.gz");
+        assert!(storage.exists("backup_1.sql.gz").await.unwrap());
+        assert!(storage.stat("backup_dir").await.unwrap().is_none());
+        storage.delete("backup_1.sql.gz").await.unwrap();
+        assert!(!storage.exists("backup_1.sql.gz").await.unwrap());
+
+        // Leases need conditional writes, which local storage does not offer
+        assert!(storage.put_if("lease.json", b"{}".to_vec(), None).await.is_err());
+
+        let _ = std::fs::remove_dir_all(&root);
+        let _ = std::fs::remove_file(&source);
+    }
+}
@@ -0,0 +1,272 @@
+use crate::config::{BackupConfig, Config, ConnectionConfig, VerifyCheck};
+use crate::restore::{self, RestoreOptions, RestoreWorkspace};
+use crate::driver::{self, BackupDriver};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, Utc};
+use serde::Serialize;
+use std::io::Write;
+use tracing::{error, info, warn};
+
+const DEFAULT_CHECK_QUERY: &str = "SELECT 1";
+const RESULTS_FILE: &str = "verifications.jsonl";
+const SCRATCH_PREFIX: &str = "dbackup_verify_";
+/// PostgreSQL truncates identifiers longer than this; MySQL allows 64
+const MAX_DATABASE_NAME_LEN: usize = 63;
+
+/// Outcome of one restore verification, appended to the results file
+#[derive(Debug, Serialize)]
+pub struct VerificationReport {
+    pub backup: String,
+    pub archive: String,
+    pub server: String,
+    pub scratch_database: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub success: bool,
+    pub checks: Vec<CheckOutcome>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CheckOutcome {
+    pub query: String,
+    pub output: String,
+    pub passed: bool,
+}
+
+/// Restore an archive (the newest one by default) into a scratch database,
+/// run the configured checks and drop the scratch database again.
+pub async fn verify_backup(
+    config: &Config,
+    backup: &BackupConfig,
+    archive: Option<&str>,
+) -> Result<VerificationReport> {
+    let verify_config = backup.verify.clone().unwrap_or_default();
+    let storage_config = config.get_storage_for_backup(backup)
+        .context(format!("Failed to resolve storage for backup '{}'", backup.name))?;
+
+    let archive = match archive {
+        Some(archive) => archive.to_string(),
+        None => restore::find_latest_archive(backup, &storage_config).await?,
+    };
+
+    let mut target = verification_server(config, backup)?;
+    target.database = scratch_database_name(&backup.name, Local::now());
+
+    let driver = driver::create_driver(backup)?;
+
+    let options = RestoreOptions {
+        target,
+        jobs: backup.parallel_jobs,
+        binary_path: config.restore_binary(driver.as_ref()),
+        sql_client_path: config.sql_client_binary(driver.as_ref()),
+        create_database: true,
+        include_system_databases: false,
+    };
+
+    let checks = if verify_config.checks.is_empty() {
+        vec![VerifyCheck { query: DEFAULT_CHECK_QUERY.to_string(), min: None }]
+    } else {
+        verify_config.checks.clone()
+    };
+
+    info!(
+        "Verifying '{}' by restoring {} into scratch database '{}' on {}:{}",
+        backup.name, archive, options.target.database, options.target.host, options.target.port
+    );
+
+    let started_at = Utc::now();
+    let mut outcomes = Vec::new();
+    let result = run_verification(driver.as_ref(), &archive, &storage_config, &options, &checks, &mut outcomes).await;
+
+    // Always drop the scratch database, even when the restore failed half-way
+    if let Err(e) = driver.drop_database(&options).await {
+        warn!("Failed to drop scratch database '{}': {}", options.target.database, e);
+    }
+
+    let error = result.err().map(|e| format!("{:#}", e));
+    Ok(VerificationReport {
+        backup: backup.name.clone(),
+        archive,
+        server: format!("{}:{}", options.target.host, options.target.port),
+        scratch_database: options.target.database.clone(),
+        started_at,
+        finished_at: Utc::now(),
+        success: error.is_none(),
+        checks: outcomes,
+        error,
+    })
+}
+
+/// Server a job's verifications restore onto: the source connection, then the
+/// named target, then inline overrides. One of the two must be configured.
+pub fn verification_server(config: &Config, backup: &BackupConfig) -> Result<ConnectionConfig> {
+    let verify_config = backup.verify.clone().unwrap_or_default();
+    if verify_config.target.is_none() && verify_config.server.is_none() {
+        anyhow::bail!(
+            "Backup '{}' has no verification server: set verify.target or verify.server",
+            backup.name
+        );
+    }
+
+    let mut target = backup.connection.clone();
+    if let Some(name) = &verify_config.target {
+        target = config.find_restore_target(name)?.apply(&target);
+    }
+    if let Some(server) = &verify_config.server {
+        target = server.apply(&target);
+    }
+
+    // A whole-server dump replays every database, not just the scratch one
+    if backup.connection.database.is_empty() && restore::is_same_database(&backup.connection, &target) {
+        anyhow::bail!(
+            "Backup '{}' dumps the whole server {}:{}, so it cannot be verified on that server; \
+             point verify.target or verify.server at another one",
+            backup.name,
+            target.host,
+            target.port
+        );
+    }
+    Ok(target)
+}
+
+async fn run_verification(
+    driver: &dyn BackupDriver,
+    archive: &str,
+    storage_config: &crate::config::StorageConfig,
+    options: &RestoreOptions,
+    checks: &[VerifyCheck],
+    outcomes: &mut Vec<CheckOutcome>,
+) -> Result<()> {
+    let workspace = RestoreWorkspace::new()?;
+    let archive_path = restore::fetch_archive(archive, storage_config, &workspace).await?;
+
+    driver.restore(&archive_path, options).await?;
+
+    let mut failed = 0;
+    for check in checks {
+        let outcome = match driver.run_query(options, &check.query).await {
+            Ok(output) => {
+                let passed = evaluate_check(check, &output);
+                CheckOutcome { query: check.query.clone(), output: output.trim().to_string(), passed }
+            }
+            Err(e) => CheckOutcome { query: check.query.clone(), output: format!("{:#}", e), passed: false },
+        };
+
+        if outcome.passed {
+            info!("  ✓ {} => {}", outcome.query, outcome.output);
+        } else {
+            error!("  ✗ {} => {}", outcome.query, outcome.output);
+            failed += 1;
+        }
+        outcomes.push(outcome);
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{} of {} check(s) failed", failed, checks.len());
+    }
+    Ok(())
+}
+
+/// A check passes if its query succeeded and, when `min` is set, the first
+/// value of the output is a number of at least `min`
+fn evaluate_check(check: &VerifyCheck, output: &str) -> bool {
+    match check.min {
+        None => true,
+        Some(min) => output
+            .split_whitespace()
+            .next()
+            .and_then(|value| value.parse::<i64>().ok())
+            .is_some_and(|value| value >= min),
+    }
+}
+
+/// Build a unique, identifier-safe scratch database name for a backup job
+fn scratch_database_name(backup_name: &str, now: DateTime<Local>) -> String {
+    let timestamp = now.format("%Y%m%d_%H%M%S").to_string();
+
+    let mut job: String = backup_name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    while job.contains("__") {
+        job = job.replace("__", "_");
+    }
+    let job = job.trim_matches('_');
+
+    let budget = MAX_DATABASE_NAME_LEN - SCRATCH_PREFIX.len() - timestamp.len() - 1;
+    let job: String = job.chars().take(budget).collect();
+    format!("{}{}_{}", SCRATCH_PREFIX, job.trim_end_matches('_'), timestamp)
+}
+
+/// Append a verification report to the results file in the state directory
+pub fn record(config: &Config, report: &VerificationReport) -> Result<()> {
+    let state_dir = config.state_dir();
+    std::fs::create_dir_all(&state_dir)
+        .context(format!("Failed to create state directory: {}", state_dir.display()))?;
+
+    let path = state_dir.join(RESULTS_FILE);
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .context(format!("Failed to open verification results: {}", path.display()))?;
+
+    let line = serde_json::to_string(report).context("Failed to serialize verification report")?;
+    writeln!(file, "{}", line).context("Failed to write verification report")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_scratch_database_name() {
+        let now = Local.with_ymd_and_hms(2026, 2, 18, 3, 0, 0).unwrap();
+        assert_eq!(
+            scratch_database_name("Production PostgreSQL - S3", now),
+            "dbackup_verify_production_postgresql_s3_20260218_030000"
+        );
+
+        let long = scratch_database_name(&"x".repeat(200), now);
+        assert!(long.len() <= MAX_DATABASE_NAME_LEN);
+        assert!(long.ends_with("_20260218_030000"));
+    }
+
+    #[test]
+    fn test_verification_server() {
+        let config = |database: &str, verify: &str| -> Config {
+            serde_yaml::from_str(&format!(
+                "backups:\n  - name: app\n    driver: mysql\n    \
+                 connection: {{host: db1, port: 3306, username: u, password: p, database: '{}'}}\n    \
+                 storage: {{driver: local, path: /tmp}}\n    verify: {}",
+                database, verify
+            ))
+            .unwrap()
+        };
+        let server = |config: &Config| verification_server(config, &config.backups[0]);
+
+        // The source server is never used implicitly
+        assert!(server(&config("app", "{checks: []}")).is_err());
+        // A scratch database next to a single-database source is fine
+        assert_eq!(server(&config("app", "{server: {database: other}}")).unwrap().host, "db1");
+        // A whole-server dump would overwrite the source server
+        assert!(server(&config("", "{server: {database: other}}")).is_err());
+        assert_eq!(server(&config("", "{server: {host: verify-db}}")).unwrap().host, "verify-db");
+    }
+
+    #[test]
+    fn test_evaluate_check() {
+        let check = VerifyCheck { query: "SELECT count(*) FROM users".to_string(), min: Some(10) };
+        assert!(evaluate_check(&check, "42\n"));
+        assert!(!evaluate_check(&check, "3\n"));
+        assert!(!evaluate_check(&check, ""));
+
+        let check = VerifyCheck { query: "SELECT 1".to_string(), min: None };
+        assert!(evaluate_check(&check, ""));
+    }
+}